@@ -0,0 +1,520 @@
+//! Derive macros for [`changed`](https://docs.rs/changed).
+//!
+//! Use them through the `derive` feature of `changed`, which re-exports them.
+
+use std::collections::HashMap;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Ident};
+
+/// Per-field change tracking for a struct with named fields.
+///
+/// Generates a `{Name}Tracked` tracker and a `{Name}Field` enum, and implements
+/// `changed::Trackable`, and `changed::AnyChanged` and `changed::CheckChanged` for the tracker. Mark a field
+/// `#[tracked(nested)]` to track it with its own derived tracker instead of a `Cd`.
+///
+/// With the `serde` feature of `changed`, mark the struct `#[tracked(delta)]` to also
+/// generate a `{Name}Delta` holding only the changed fields, and implement `changed::Delta`.
+/// Nested fields need `#[tracked(delta)]` as well.
+///
+/// ```
+/// use changed::{Trackable, Tracked};
+///
+/// #[derive(Tracked)]
+/// struct Position {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// #[derive(Tracked)]
+/// struct Player {
+///     hp: i32,
+///     name: String,
+///     #[tracked(nested)]
+///     position: Position,
+/// }
+///
+/// let mut player = Player {
+///     hp: 10,
+///     name: "Ferris".to_string(),
+///     position: Position { x: 0, y: 0 },
+/// }
+/// .track();
+///
+/// *player.hp_mut() -= 1;
+/// *player.position_mut().x_mut() += 1;
+/// let changed: Vec<_> = player.changed_fields().collect();
+/// assert_eq!(changed, [PlayerField::Hp, PlayerField::Position]);
+///
+/// player.reset_all();
+/// assert!(!player.changed());
+/// ```
+#[proc_macro_derive(Tracked, attributes(tracked))]
+pub fn derive_tracked(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    tracked(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct Field {
+    ident: Ident,
+    variant: Ident,
+    vis: syn::Visibility,
+    ty: syn::Type,
+    nested: bool,
+}
+
+fn tracked(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let named = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => named,
+            _ => {
+                return Err(Error::new(
+                    Span::call_site(),
+                    "#[derive(Tracked)] needs a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "#[derive(Tracked)] only works on structs",
+            ))
+        }
+    };
+
+    let mut delta = false;
+    for attr in &input.attrs {
+        if attr.path().is_ident("tracked") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("delta") {
+                    delta = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `delta`"))
+                }
+            })?;
+        }
+    }
+
+    let mut fields = Vec::new();
+    for field in &named.named {
+        let mut nested = false;
+        for attr in &field.attrs {
+            if attr.path().is_ident("tracked") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("nested") {
+                        nested = true;
+                        Ok(())
+                    } else {
+                        Err(meta.error("expected `nested`"))
+                    }
+                })?;
+            }
+        }
+        let ident = field.ident.clone().expect("named field");
+        fields.push(Field {
+            variant: Ident::new(&camel_case(&ident.unraw().to_string()), ident.span()),
+            ident,
+            vis: field.vis.clone(),
+            ty: field.ty.clone(),
+            nested,
+        });
+    }
+    check_names(&fields)?;
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let tracked = format_ident!("{}Tracked", name);
+    let field_enum = format_ident!("{}Field", name);
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let generics = &input.generics;
+
+    let count = fields.len();
+    let variants: Vec<_> = fields.iter().map(|f| &f.variant).collect();
+    let names: Vec<_> = fields.iter().map(|f| f.ident.unraw().to_string()).collect();
+
+    let storage = fields.iter().map(|f| {
+        let (ident, ty) = (&f.ident, &f.ty);
+        if f.nested {
+            quote!(#ident: <#ty as ::changed::Trackable>::Tracked)
+        } else {
+            quote!(#ident: ::changed::Cd<#ty>)
+        }
+    });
+    let track = fields.iter().map(|f| {
+        let ident = &f.ident;
+        if f.nested {
+            quote!(#ident: ::changed::Trackable::track(value.#ident))
+        } else {
+            quote!(#ident: ::changed::Cd::new(value.#ident))
+        }
+    });
+    let untrack = fields.iter().map(|f| {
+        let ident = &f.ident;
+        if f.nested {
+            quote!(#ident: ::changed::TrackedFields::into_inner(self.#ident))
+        } else {
+            quote!(#ident: self.#ident.take())
+        }
+    });
+    let field_changed: Vec<_> = fields
+        .iter()
+        .map(|f| {
+            let ident = &f.ident;
+            if f.nested {
+                quote!(::changed::TrackedFields::changed(&self.#ident))
+            } else {
+                quote!(self.#ident.changed())
+            }
+        })
+        .collect();
+    let field_reset: Vec<_> = fields
+        .iter()
+        .map(|f| {
+            let ident = &f.ident;
+            if f.nested {
+                quote!(::changed::TrackedFields::reset_all(&mut self.#ident))
+            } else {
+                quote!(self.#ident.reset())
+            }
+        })
+        .collect();
+    let accessors = fields.iter().zip(&field_changed).zip(&field_reset).map(
+        |((f, changed), reset)| {
+            let (ident, ty, vis) = (&f.ident, &f.ty, &f.vis);
+            let raw = ident.unraw();
+            let get_mut = format_ident!("{}_mut", raw);
+            let get_mut_silently = format_ident!("{}_mut_silently", raw);
+            let is_changed = format_ident!("{}_changed", raw);
+            let reset_field = format_ident!("reset_{}", raw);
+            let mut out = if f.nested {
+                let tracker = quote!(<#ty as ::changed::Trackable>::Tracked);
+                quote! {
+                    #[doc = concat!("The tracker of `", stringify!(#raw), "`.")]
+                    #vis fn #ident(&self) -> &#tracker {
+                        &self.#ident
+                    }
+
+                    #[doc = concat!("The tracker of `", stringify!(#raw), "`, to mutate it.")]
+                    #vis fn #get_mut(&mut self) -> &mut #tracker {
+                        &mut self.#ident
+                    }
+                }
+            } else {
+                quote! {
+                    #[doc = concat!("Read `", stringify!(#raw), "`. Does not trip change detection.")]
+                    #vis fn #ident(&self) -> &#ty {
+                        &self.#ident
+                    }
+
+                    #[doc = concat!("Mutate `", stringify!(#raw), "`. Trips change detection for it.")]
+                    #vis fn #get_mut(&mut self) -> &mut #ty {
+                        &mut self.#ident
+                    }
+
+                    #[doc = concat!("Mutate `", stringify!(#raw), "` without tripping change detection.")]
+                    #vis fn #get_mut_silently(&mut self) -> &mut #ty {
+                        self.#ident.mutate_silently()
+                    }
+                }
+            };
+            out.extend(quote! {
+                #[doc = concat!("Check if `", stringify!(#raw), "` has been changed since the last call to reset (or created.)")]
+                #vis fn #is_changed(&self) -> bool {
+                    #changed
+                }
+
+                #[doc = concat!("Reset the change tracking of `", stringify!(#raw), "` to false.")]
+                #vis fn #reset_field(&mut self) {
+                    #reset
+                }
+            });
+            out
+        },
+    );
+
+    let delta_impl = if delta {
+        delta_impl(&input, &fields)
+    } else {
+        TokenStream2::new()
+    };
+
+    Ok(quote! {
+        #[doc = concat!("A field of [`", stringify!(#name), "`].")]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #vis enum #field_enum {
+            #(
+                #[doc = concat!("`", #names, "`")]
+                #variants,
+            )*
+        }
+
+        impl #field_enum {
+            /// The name of the field.
+            #vis fn name(self) -> &'static str {
+                match self {
+                    #(#field_enum::#variants => #names,)*
+                }
+            }
+        }
+
+        #[doc = concat!("Tracks changes to each field of [`", stringify!(#name), "`].")]
+        #vis struct #tracked #generics #where_clause {
+            #(#storage,)*
+        }
+
+        impl #impl_generics #tracked #ty_generics #where_clause {
+            #(#accessors)*
+
+            /// The fields that have been changed since they were last reset (or created.)
+            #vis fn changed_fields(&self) -> impl Iterator<Item = #field_enum> {
+                let changed: [(#field_enum, bool); #count] = [#((#field_enum::#variants, #field_changed)),*];
+                ::core::iter::IntoIterator::into_iter(changed)
+                    .filter(|(_, changed)| *changed)
+                    .map(|(field, _)| field)
+            }
+
+            /// Check if any field has been changed since the last call to reset (or created.)
+            #vis fn changed(&self) -> bool {
+                false #(|| #field_changed)*
+            }
+
+            /// Reset the change tracking of every field to false.
+            #vis fn reset_all(&mut self) {
+                #(#field_reset;)*
+            }
+
+            /// Stop tracking changes and return the struct.
+            #vis fn into_inner(self) -> #name #ty_generics {
+                #name {
+                    #(#untrack,)*
+                }
+            }
+        }
+
+        impl #impl_generics ::changed::TrackedFields for #tracked #ty_generics #where_clause {
+            type Value = #name #ty_generics;
+
+            fn changed(&self) -> bool {
+                #tracked::changed(self)
+            }
+
+            fn reset_all(&mut self) {
+                #tracked::reset_all(self)
+            }
+
+            fn into_inner(self) -> Self::Value {
+                #tracked::into_inner(self)
+            }
+        }
+
+        impl #impl_generics ::changed::CheckChanged for #tracked #ty_generics #where_clause {
+            fn changed(&self) -> bool {
+                #tracked::changed(self)
+            }
+        }
+
+        impl #impl_generics ::changed::AnyChanged for #tracked #ty_generics #where_clause {
+            fn reset(&mut self) {
+                #tracked::reset_all(self)
+            }
+        }
+
+        impl #impl_generics ::changed::Trackable for #name #ty_generics #where_clause {
+            type Tracked = #tracked #ty_generics;
+
+            fn track(self) -> Self::Tracked {
+                let value = self;
+                #tracked {
+                    #(#track,)*
+                }
+            }
+        }
+
+        #delta_impl
+    })
+}
+
+fn delta_impl(input: &DeriveInput, fields: &[Field]) -> TokenStream2 {
+    let vis = &input.vis;
+    let name = &input.ident;
+    let tracked = format_ident!("{}Tracked", name);
+    let delta = format_ident!("{}Delta", name);
+    let (impl_generics, ty_generics, _) = input.generics.split_for_impl();
+    let params = &input.generics.params;
+
+    let idents: Vec<_> = fields.iter().map(|f| &f.ident).collect();
+    let trackers: Vec<_> = fields
+        .iter()
+        .map(|f| {
+            let ty = &f.ty;
+            if f.nested {
+                quote!(<#ty as ::changed::Trackable>::Tracked)
+            } else {
+                quote!(::changed::Cd<#ty>)
+            }
+        })
+        .collect();
+    let storage = fields.iter().zip(&trackers).map(|(f, tracker)| {
+        let (ident, vis) = (&f.ident, &f.vis);
+        quote! {
+            #[serde(default, skip_serializing_if = "::core::option::Option::is_none")]
+            #vis #ident: ::core::option::Option<<#tracker as ::changed::Delta>::Delta>
+        }
+    });
+
+    // The struct's own bounds, plus every tracker being a `Delta`.
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for tracker in &trackers {
+        where_clause
+            .predicates
+            .push(syn::parse_quote!(#tracker: ::changed::Delta));
+    }
+
+    quote! {
+        #[doc = concat!("The fields of [`", stringify!(#name), "`] that changed, from `changed::Delta`.")]
+        #[derive(::changed::__serde::Serialize, ::changed::__serde::Deserialize)]
+        #[serde(crate = "::changed::__serde", bound = "")]
+        #vis struct #delta <#params> #where_clause {
+            #(#storage,)*
+        }
+
+        impl #impl_generics ::changed::Delta for #tracked #ty_generics #where_clause {
+            type Delta = #delta #ty_generics;
+
+            fn serde_delta(&self) -> ::core::option::Option<Self::Delta> {
+                if !#tracked::changed(self) {
+                    return ::core::option::Option::None;
+                }
+                ::core::option::Option::Some(#delta {
+                    #(#idents: ::changed::Delta::serde_delta(&self.#idents),)*
+                })
+            }
+
+            fn apply_delta(&mut self, delta: Self::Delta) {
+                #(
+                    if let ::core::option::Option::Some(delta) = delta.#idents {
+                        ::changed::Delta::apply_delta(&mut self.#idents, delta);
+                    }
+                )*
+            }
+        }
+    }
+}
+
+/// Methods of the tracker that are not generated per field.
+const TRACKER_METHODS: [&str; 4] = ["changed_fields", "changed", "reset_all", "into_inner"];
+
+/// Report generated names that would be defined twice on the field that causes it,
+/// rather than as an error inside the generated code.
+fn check_names(fields: &[Field]) -> syn::Result<()> {
+    let mut methods: HashMap<String, Option<&Ident>> = TRACKER_METHODS
+        .iter()
+        .map(|method| (method.to_string(), None))
+        .collect();
+    let mut variants = HashMap::new();
+    for field in fields {
+        let raw = field.ident.unraw();
+        let mut names = vec![
+            raw.to_string(),
+            format!("{}_mut", raw),
+            format!("{}_changed", raw),
+            format!("reset_{}", raw),
+        ];
+        if !field.nested {
+            names.push(format!("{}_mut_silently", raw));
+        }
+        for name in names {
+            match methods.insert(name.clone(), Some(&field.ident)) {
+                None => {}
+                Some(None) => {
+                    return Err(Error::new(
+                        field.ident.span(),
+                        format!("the tracker already has a method named `{}`", name),
+                    ))
+                }
+                Some(Some(other)) => {
+                    return Err(Error::new(
+                        field.ident.span(),
+                        format!(
+                            "the method `{}` for this field clashes with one for `{}`",
+                            name,
+                            other.unraw()
+                        ),
+                    ))
+                }
+            }
+        }
+        if let Some(other) = variants.insert(field.variant.to_string(), &field.ident) {
+            return Err(Error::new(
+                field.ident.span(),
+                format!(
+                    "this field and `{}` would both be the variant `{}`",
+                    other.unraw(),
+                    field.variant
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn camel_case(snake: &str) -> String {
+    snake
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tracked;
+    use syn::parse_quote;
+
+    fn error(input: syn::DeriveInput) -> String {
+        tracked(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn clashing_names_are_errors() {
+        assert_eq!(
+            error(parse_quote!(
+                struct S {
+                    changed: bool,
+                }
+            )),
+            "the tracker already has a method named `changed`"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                struct S {
+                    hp: i32,
+                    hp_mut: i32,
+                }
+            )),
+            "the method `hp_mut` for this field clashes with one for `hp`"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                struct S {
+                    x_1: i32,
+                    x1: i32,
+                }
+            )),
+            "this field and `x_1` would both be the variant `X1`"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use changed::{Delta, Trackable, Tracked, TrackedFields};
+
+#[derive(Debug, PartialEq, Tracked)]
+#[tracked(delta)]
+struct Vec2 {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Debug, PartialEq, Tracked)]
+#[tracked(delta)]
+pub struct Entity {
+    pub name: String,
+    health: u32,
+    r#type: u8,
+    #[tracked(nested)]
+    velocity: Vec2,
+}
+
+#[derive(Tracked)]
+#[tracked(delta)]
+struct Wrapper<T: Clone> {
+    inner: T,
+}
+
+fn entity() -> Entity {
+    Entity {
+        name: "crab".to_string(),
+        health: 3,
+        r#type: 1,
+        velocity: Vec2 { x: 0.0, y: 0.0 },
+    }
+}
+
+#[test]
+fn starts_unchanged() {
+    let tracked = entity().track();
+    assert!(!tracked.changed());
+    assert_eq!(tracked.changed_fields().count(), 0);
+}
+
+#[test]
+fn tracks_fields_separately() {
+    let mut tracked = entity().track();
+    tracked.name_mut().push('s');
+    *tracked.type_mut() = 2;
+    assert!(tracked.name_changed());
+    assert!(!tracked.health_changed());
+    assert!(tracked.type_changed());
+    assert_eq!(
+        tracked.changed_fields().collect::<Vec<_>>(),
+        [EntityField::Name, EntityField::Type]
+    );
+    assert_eq!(EntityField::Type.name(), "type");
+
+    tracked.reset_name();
+    assert_eq!(
+        tracked.changed_fields().collect::<Vec<_>>(),
+        [EntityField::Type]
+    );
+}
+
+#[test]
+fn reads_and_silent_mutations_do_not_trip() {
+    let mut tracked = entity().track();
+    assert_eq!(*tracked.health(), 3);
+    *tracked.health_mut_silently() -= 1;
+    assert!(!tracked.changed());
+    assert_eq!(*tracked.health(), 2);
+}
+
+#[test]
+fn nested_fields_report_to_their_parent() {
+    let mut tracked = entity().track();
+    *tracked.velocity_mut().y_mut() = 1.0;
+    assert!(tracked.velocity_changed());
+    assert_eq!(
+        tracked.velocity().changed_fields().collect::<Vec<_>>(),
+        [Vec2Field::Y]
+    );
+    assert_eq!(
+        tracked.changed_fields().collect::<Vec<_>>(),
+        [EntityField::Velocity]
+    );
+
+    tracked.reset_all();
+    assert!(!tracked.velocity().y_changed());
+    assert!(!TrackedFields::changed(&tracked));
+}
+
+#[test]
+fn round_trips_through_the_tracker() {
+    let mut tracked = entity().track();
+    *tracked.health_mut() = 10;
+    let mut expected = entity();
+    expected.health = 10;
+    assert_eq!(tracked.into_inner(), expected);
+}
+
+#[test]
+fn generic_structs() {
+    let mut tracked = Wrapper { inner: vec![1] }.track();
+    tracked.inner_mut().push(2);
+    assert!(tracked.inner_changed());
+    assert_eq!(tracked.into_inner().inner, [1, 2]);
+}
+
+#[test]
+fn trackers_group_with_other_trackers() {
+    use changed::{AnyChanged, Cd, CheckChanged};
+
+    let mut group = (entity().track(), vec![Cd::new(1)]);
+    group.0.velocity_mut().x_mut();
+    assert!(group.changed());
+    group.reset();
+    assert!(!group.0.velocity().changed());
+}
+
+#[test]
+fn deltas_only_hold_changed_fields() {
+    let mut sender = entity().track();
+    let mut receiver = entity().track();
+    assert!(sender.serde_delta().is_none());
+
+    *sender.type_mut() = 2;
+    *sender.velocity_mut().x_mut() = 1.5;
+    let json = serde_json::to_string(&sender.serde_delta()).unwrap();
+    assert_eq!(json, r#"{"type":2,"velocity":{"x":1.5}}"#);
+
+    receiver.apply_delta(
+        serde_json::from_str::<Option<EntityDelta>>(&json)
+            .unwrap()
+            .unwrap(),
+    );
+    assert_eq!(
+        receiver.changed_fields().collect::<Vec<_>>(),
+        [EntityField::Type, EntityField::Velocity]
+    );
+    assert_eq!(receiver.into_inner(), sender.into_inner());
+}
+
+#[test]
+fn generic_deltas() {
+    let mut sender = Wrapper { inner: vec![1] }.track();
+    let mut receiver = Wrapper { inner: vec![1] }.track();
+    sender.inner_mut().push(2);
+    receiver.apply_delta(sender.serde_delta().unwrap());
+    assert_eq!(receiver.into_inner().inner, [1, 2]);
+}
@@ -0,0 +1,357 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::reactive::Signal;
+use crate::{
+    Cd, CdHistory, CdMap, CdObserved, CdSet, CdTree, CdVec, CdWatch, ChangeDetector, MapBackend,
+    SetBackend, SyncCd,
+};
+
+/// Change tracking that can be checked without knowing what is tracked.
+///
+/// Implemented for everything that implements [`AnyChanged`], and also for shared
+/// references to those, so a group of borrowed trackers can be checked from a `&self` method.
+/// ```
+/// use changed::{Cd, CheckChanged};
+///
+/// struct Player {
+///     hp: Cd<i32>,
+///     name: Cd<String>,
+/// }
+///
+/// impl Player {
+///     fn needs_redraw(&self) -> bool {
+///         (&self.hp, &self.name).changed()
+///     }
+/// }
+///
+/// let mut player = Player { hp: Cd::new(10), name: Cd::new("Ferris".to_string()) };
+/// assert!(!player.needs_redraw());
+/// *player.hp -= 1;
+/// assert!(player.needs_redraw());
+/// ```
+pub trait CheckChanged {
+    /// Check if anything has been changed since the last call to reset (or created.)
+    fn changed(&self) -> bool;
+}
+
+/// Change tracking that can be checked and reset without knowing what is tracked.
+///
+/// Implemented for [`Cd`], the other tracker types of this crate, [`Signal`] and the trackers
+/// generated by `#[derive(Tracked)]`, and for tuples (up to 12), arrays, slices,
+/// `Vec`, `Option`, `Box` and `Rc<RefCell<_>>` of those, so a group of them
+/// can be handled at once. [`query`](crate::query) inputs are tracked by the database instead.
+///
+/// Checking is in [`CheckChanged`], which also covers `&` references.
+/// ```
+/// use changed::{AnyChanged, Cd, CheckChanged};
+///
+/// let mut a = Cd::new(1);
+/// let mut b = Cd::new("b");
+/// let mut list = vec![Cd::new(1.0), Cd::new(2.0)];
+///
+/// *list[1] += 1.0;
+/// assert!((&a, &b, &list).changed());
+/// assert!(!(&a, &b).changed());
+///
+/// (&mut a, &mut list).reset();
+/// assert!(!list.changed());
+/// ```
+pub trait AnyChanged: CheckChanged {
+    /// Reset all of the change tracking to false.
+    fn reset(&mut self);
+}
+
+impl<T, D: ChangeDetector<T>> CheckChanged for Cd<T, D> {
+    fn changed(&self) -> bool {
+        Cd::changed(self)
+    }
+}
+
+impl<T, D: ChangeDetector<T>> AnyChanged for Cd<T, D> {
+    fn reset(&mut self) {
+        Cd::reset(self)
+    }
+}
+
+impl<T> CheckChanged for CdVec<T> {
+    fn changed(&self) -> bool {
+        CdVec::changed(self)
+    }
+}
+
+impl<T> AnyChanged for CdVec<T> {
+    fn reset(&mut self) {
+        CdVec::reset(self)
+    }
+}
+
+impl<K: Clone, V, M: MapBackend<K, V>> CheckChanged for CdMap<K, V, M> {
+    fn changed(&self) -> bool {
+        CdMap::changed(self)
+    }
+}
+
+impl<K: Clone, V, M: MapBackend<K, V>> AnyChanged for CdMap<K, V, M> {
+    fn reset(&mut self) {
+        CdMap::reset(self)
+    }
+}
+
+impl<T: Clone, S: SetBackend<T>> CheckChanged for CdSet<T, S> {
+    fn changed(&self) -> bool {
+        CdSet::changed(self)
+    }
+}
+
+impl<T: Clone, S: SetBackend<T>> AnyChanged for CdSet<T, S> {
+    fn reset(&mut self) {
+        CdSet::reset(self)
+    }
+}
+
+impl<T: Clone> CheckChanged for CdHistory<T> {
+    fn changed(&self) -> bool {
+        CdHistory::changed(self)
+    }
+}
+
+/// Resetting commits a snapshot, like [`CdHistory::reset()`].
+impl<T: Clone> AnyChanged for CdHistory<T> {
+    fn reset(&mut self) {
+        CdHistory::reset(self)
+    }
+}
+
+/// Checks and resets the whole tree.
+impl<T> CheckChanged for CdTree<T> {
+    fn changed(&self) -> bool {
+        self.subtree_changed(self.root())
+    }
+}
+
+impl<T> AnyChanged for CdTree<T> {
+    fn reset(&mut self) {
+        self.reset_all()
+    }
+}
+
+impl<T, D: ChangeDetector<T>> CheckChanged for CdObserved<T, D> {
+    fn changed(&self) -> bool {
+        CdObserved::changed(self)
+    }
+}
+
+impl<T, D: ChangeDetector<T>> AnyChanged for CdObserved<T, D> {
+    fn reset(&mut self) {
+        CdObserved::reset(self)
+    }
+}
+
+impl<T, D: ChangeDetector<T>> CheckChanged for CdWatch<T, D> {
+    fn changed(&self) -> bool {
+        CdWatch::changed(self)
+    }
+}
+
+impl<T, D: ChangeDetector<T>> AnyChanged for CdWatch<T, D> {
+    fn reset(&mut self) {
+        CdWatch::reset(self)
+    }
+}
+
+impl<T> CheckChanged for SyncCd<T> {
+    fn changed(&self) -> bool {
+        SyncCd::changed(self)
+    }
+}
+
+impl<T> AnyChanged for SyncCd<T> {
+    fn reset(&mut self) {
+        SyncCd::reset(self)
+    }
+}
+
+impl<T> CheckChanged for Signal<T> {
+    fn changed(&self) -> bool {
+        Signal::changed(self)
+    }
+}
+
+/// Resetting does not rerun anything, like [`Signal::reset()`].
+/// ```
+/// use changed::reactive::Signal;
+/// use changed::{AnyChanged, Cd, CheckChanged};
+///
+/// let mut group = (Signal::new(1), Cd::new(2));
+/// group.0.set(3);
+/// assert!(group.changed());
+/// group.reset();
+/// assert!(!group.0.changed());
+/// ```
+impl<T> AnyChanged for Signal<T> {
+    fn reset(&mut self) {
+        Signal::reset(self)
+    }
+}
+
+impl<A: CheckChanged + ?Sized> CheckChanged for &A {
+    fn changed(&self) -> bool {
+        (**self).changed()
+    }
+}
+
+impl<A: CheckChanged + ?Sized> CheckChanged for &mut A {
+    fn changed(&self) -> bool {
+        (**self).changed()
+    }
+}
+
+impl<A: AnyChanged + ?Sized> AnyChanged for &mut A {
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+}
+
+impl<A: CheckChanged + ?Sized> CheckChanged for Box<A> {
+    fn changed(&self) -> bool {
+        (**self).changed()
+    }
+}
+
+impl<A: AnyChanged + ?Sized> AnyChanged for Box<A> {
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+}
+
+/// Borrows the `RefCell`, so it panics if it is already mutably borrowed.
+/// ```
+/// use changed::{AnyChanged, Cd, CheckChanged};
+/// use std::{cell::RefCell, rc::Rc};
+///
+/// let shared = Rc::new(RefCell::new(Cd::new(5)));
+/// let mut handle = shared.clone();
+/// **shared.borrow_mut() += 1;
+/// assert!(handle.changed());
+/// handle.reset();
+/// assert!(!shared.borrow().changed());
+/// ```
+impl<A: CheckChanged + ?Sized> CheckChanged for Rc<RefCell<A>> {
+    fn changed(&self) -> bool {
+        self.borrow().changed()
+    }
+}
+
+impl<A: AnyChanged + ?Sized> AnyChanged for Rc<RefCell<A>> {
+    fn reset(&mut self) {
+        self.borrow_mut().reset()
+    }
+}
+
+impl<A: CheckChanged> CheckChanged for Option<A> {
+    fn changed(&self) -> bool {
+        self.as_ref().is_some_and(A::changed)
+    }
+}
+
+impl<A: AnyChanged> AnyChanged for Option<A> {
+    fn reset(&mut self) {
+        if let Some(tracked) = self {
+            tracked.reset();
+        }
+    }
+}
+
+impl<A: CheckChanged> CheckChanged for [A] {
+    fn changed(&self) -> bool {
+        self.iter().any(A::changed)
+    }
+}
+
+impl<A: AnyChanged> AnyChanged for [A] {
+    fn reset(&mut self) {
+        self.iter_mut().for_each(A::reset)
+    }
+}
+
+impl<A: CheckChanged, const N: usize> CheckChanged for [A; N] {
+    fn changed(&self) -> bool {
+        self[..].changed()
+    }
+}
+
+impl<A: AnyChanged, const N: usize> AnyChanged for [A; N] {
+    fn reset(&mut self) {
+        self[..].reset()
+    }
+}
+
+impl<A: CheckChanged> CheckChanged for Vec<A> {
+    fn changed(&self) -> bool {
+        self[..].changed()
+    }
+}
+
+impl<A: AnyChanged> AnyChanged for Vec<A> {
+    fn reset(&mut self) {
+        self[..].reset()
+    }
+}
+
+macro_rules! tuple_impls {
+    ($($name:ident)+) => {
+        impl<$($name: CheckChanged),+> CheckChanged for ($($name,)+) {
+            fn changed(&self) -> bool {
+                #[allow(non_snake_case)]
+                let ($($name,)+) = self;
+                false $(|| $name.changed())+
+            }
+        }
+
+        impl<$($name: AnyChanged),+> AnyChanged for ($($name,)+) {
+            fn reset(&mut self) {
+                #[allow(non_snake_case)]
+                let ($($name,)+) = self;
+                $($name.reset();)+
+            }
+        }
+    };
+}
+
+tuple_impls! { A }
+tuple_impls! { A B }
+tuple_impls! { A B C }
+tuple_impls! { A B C D }
+tuple_impls! { A B C D E }
+tuple_impls! { A B C D E F }
+tuple_impls! { A B C D E F G }
+tuple_impls! { A B C D E F G H }
+tuple_impls! { A B C D E F G H I }
+tuple_impls! { A B C D E F G H I J }
+tuple_impls! { A B C D E F G H I J K }
+tuple_impls! { A B C D E F G H I J K L }
+
+#[cfg(test)]
+mod tests {
+    use crate::{AnyChanged, Cd, CdVec, CheckChanged};
+
+    #[test]
+    fn nested_containers() {
+        let mut tracked = (
+            [Cd::new(0), Cd::new(1)],
+            Some(Box::new(CdVec::from(vec![1, 2]))),
+            None::<Cd<i32>>,
+            vec![(Cd::new('a'),)],
+        );
+        assert!(!tracked.changed());
+
+        tracked.1.as_mut().unwrap()[0] += 1;
+        *tracked.3[0].0 = 'b';
+        assert!(tracked.changed());
+
+        tracked.reset();
+        assert!(!tracked.changed());
+        assert!(!tracked.3[0].0.changed());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{Baseline, Cd, ChangeDetector};
+
+/// CdApprox: Change Detection with a tolerance
+///
+/// Like [`CdEq`](crate::CdEq), but values within a tolerance of the snapshot taken at the
+/// last reset are not a change. Since drift is measured from that snapshot rather than
+/// from the previous value, slow drift still registers once it adds up past the tolerance.
+///
+/// ```
+/// use changed::CdApprox;
+/// let mut reading = CdApprox::with_tolerance(20.0, 0.5, 0.0);
+/// *reading = 20.3;
+/// assert!(!reading.changed());
+/// *reading = 20.4;
+/// assert!(!reading.changed());
+/// *reading = 20.6;
+/// assert!(reading.changed());
+/// ```
+pub type CdApprox<T> = Cd<T, ApproxDetector<T>>;
+
+/// Values that can be compared within a tolerance. Implemented for `f32`, `f64` and arrays of them.
+///
+/// Two NaNs are equal, so a reading that stays NaN is not a change,
+/// but NaN and a number are never equal.
+pub trait Approx: Clone {
+    /// Whether `self` and `other` differ by at most `abs`, or by at most `rel` times
+    /// the larger of the two magnitudes.
+    fn approx_eq(&self, other: &Self, abs: f64, rel: f64) -> bool;
+}
+
+impl Approx for f64 {
+    fn approx_eq(&self, other: &Self, abs: f64, rel: f64) -> bool {
+        if self.is_nan() || other.is_nan() {
+            return self.is_nan() && other.is_nan();
+        }
+        if self == other {
+            return true;
+        }
+        if self.is_infinite() || other.is_infinite() {
+            return false;
+        }
+        let diff = (self - other).abs();
+        diff <= abs || diff <= rel * self.abs().max(other.abs())
+    }
+}
+
+impl Approx for f32 {
+    fn approx_eq(&self, other: &Self, abs: f64, rel: f64) -> bool {
+        f64::from(*self).approx_eq(&f64::from(*other), abs, rel)
+    }
+}
+
+impl<A: Approx, const N: usize> Approx for [A; N] {
+    fn approx_eq(&self, other: &Self, abs: f64, rel: f64) -> bool {
+        self.iter()
+            .zip(other)
+            .all(|(a, b)| a.approx_eq(b, abs, rel))
+    }
+}
+
+/// Compares the data against a clone taken at the last reset, within a tolerance.
+#[derive(Debug, Clone)]
+pub struct ApproxDetector<T> {
+    baseline: Option<T>,
+    abs: f64,
+    rel: f64,
+    forced: bool,
+}
+
+impl<T> ApproxDetector<T> {
+    /// Create an ApproxDetector that allows a difference of up to `abs`,
+    /// or up to `rel` times the larger magnitude, whichever is more lenient.
+    pub fn new(abs: f64, rel: f64) -> ApproxDetector<T> {
+        ApproxDetector {
+            baseline: None,
+            abs,
+            rel,
+            forced: false,
+        }
+    }
+
+    /// Create an ApproxDetector that allows a difference of up to `abs`.
+    pub fn absolute(abs: f64) -> ApproxDetector<T> {
+        ApproxDetector::new(abs, 0.0)
+    }
+
+    /// Create an ApproxDetector that allows a difference of up to `rel` times the larger magnitude.
+    /// ```
+    /// use changed::{ApproxDetector, Cd};
+    /// let mut cd = Cd::with_detector(1000.0, ApproxDetector::relative(0.01));
+    /// *cd = 1009.0;
+    /// assert!(!cd.changed());
+    /// *cd = 1011.0;
+    /// assert!(cd.changed());
+    /// ```
+    pub fn relative(rel: f64) -> ApproxDetector<T> {
+        ApproxDetector::new(0.0, rel)
+    }
+
+    /// The snapshot taken at the last reset, if there has been one.
+    pub fn baseline(&self) -> Option<&T> {
+        self.baseline.as_ref()
+    }
+}
+
+impl<T: Approx> Cd<T, ApproxDetector<T>> {
+    /// Create a new CdApprox with data, with the tolerances of [`ApproxDetector::new()`].
+    /// It is initialized to false for change detection.
+    pub fn with_tolerance(data: T, abs: f64, rel: f64) -> Self {
+        Cd::with_detector(data, ApproxDetector::new(abs, rel))
+    }
+}
+
+impl<T: Approx> ChangeDetector<T> for ApproxDetector<T> {
+    fn on_mutate(&mut self, _data: &T) {}
+
+    fn is_changed(&self, data: &T) -> bool {
+        self.forced
+            || !self
+                .baseline
+                .as_ref()
+                .is_some_and(|baseline| baseline.approx_eq(data, self.abs, self.rel))
+    }
+
+    fn on_reset(&mut self, data: &T) {
+        match &mut self.baseline {
+            Some(baseline) => baseline.clone_from(data),
+            None => self.baseline = Some(data.clone()),
+        }
+        self.forced = false;
+    }
+
+    fn mark_changed(&mut self) {
+        self.forced = true;
+    }
+}
+
+impl<T: Approx> Baseline<T> for ApproxDetector<T> {
+    fn baseline<'a>(&'a self, data: &'a T) -> &'a T {
+        self.baseline.as_ref().unwrap_or(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ApproxDetector, Cd};
+
+    #[test]
+    fn nan_is_only_equal_to_nan() {
+        let mut cd = Cd::with_detector(f64::NAN, ApproxDetector::absolute(1.0));
+        *cd = f64::NAN;
+        assert!(!cd.changed());
+        *cd = 0.0;
+        assert!(cd.changed());
+        cd.reset();
+        *cd = f64::NAN;
+        assert!(cd.changed());
+    }
+
+    #[test]
+    fn infinities() {
+        let mut cd = Cd::with_detector(f32::INFINITY, ApproxDetector::relative(0.5));
+        *cd = f32::INFINITY;
+        assert!(!cd.changed());
+        *cd = f32::NEG_INFINITY;
+        assert!(cd.changed());
+    }
+
+    #[test]
+    fn drift_adds_up_from_the_baseline() {
+        let mut cd = Cd::with_detector([0.0f32; 3], ApproxDetector::absolute(0.25));
+        for _ in 0..2 {
+            cd[1] += 0.1;
+            assert!(!cd.changed());
+        }
+        cd[1] += 0.1;
+        assert!(cd.changed());
+        cd.reset();
+        cd[2] -= 0.2;
+        assert!(!cd.changed());
+    }
+}
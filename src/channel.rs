@@ -0,0 +1,116 @@
+use crate::{Cd, ChangeDetector};
+
+/// CdChannels: Change Detection with `N` independent dirty channels
+///
+/// `deref_mut()` sets every channel, and each consumer checks and clears only its own
+/// with [`changed_in()`](Cd::changed_in()) and [`reset_channel()`](Cd::reset_channel()).
+/// [`changed()`](Cd::changed()) reports whether any channel is set, and
+/// [`reset()`](Cd::reset()) clears all of them.
+///
+/// ```
+/// use changed::{Cd, CdChannels, ChannelDetector};
+///
+/// const REDRAW: usize = 0;
+/// const SAVE: usize = 1;
+/// const SEND: usize = 2;
+///
+/// let mut cd: CdChannels<i32, 3> = Cd::with_detector(5, ChannelDetector::new());
+/// *cd += 1;
+///
+/// cd.reset_channel(REDRAW);
+/// assert!(!cd.changed_in(REDRAW));
+/// assert!(cd.changed_in(SAVE));
+/// assert!(cd.changed_in(SEND));
+/// assert!(cd.changed());
+/// ```
+pub type CdChannels<T, const N: usize> = Cd<T, ChannelDetector<N>>;
+
+/// One dirty bit per channel, all set by `deref_mut()`.
+#[derive(Debug, Clone)]
+pub struct ChannelDetector<const N: usize> {
+    dirty: [bool; N],
+}
+
+impl<const N: usize> ChannelDetector<N> {
+    /// Create a ChannelDetector with all channels clear.
+    pub fn new() -> ChannelDetector<N> {
+        ChannelDetector { dirty: [false; N] }
+    }
+
+    /// Whether `channel` is set.
+    ///
+    /// # Panics
+    /// If `channel >= N`.
+    pub fn is_set(&self, channel: usize) -> bool {
+        self.dirty[channel]
+    }
+
+    /// Clear `channel`.
+    ///
+    /// # Panics
+    /// If `channel >= N`.
+    pub fn clear(&mut self, channel: usize) {
+        self.dirty[channel] = false;
+    }
+}
+
+impl<const N: usize> Default for ChannelDetector<N> {
+    fn default() -> Self {
+        ChannelDetector::new()
+    }
+}
+
+impl<T, const N: usize> ChangeDetector<T> for ChannelDetector<N> {
+    fn on_mutate(&mut self, _data: &T) {
+        self.dirty = [true; N];
+    }
+
+    fn is_changed(&self, _data: &T) -> bool {
+        self.dirty.iter().any(|&dirty| dirty)
+    }
+
+    fn on_reset(&mut self, _data: &T) {
+        self.dirty = [false; N];
+    }
+
+    fn mark_changed(&mut self) {
+        self.dirty = [true; N];
+    }
+}
+
+impl<T, const N: usize> Cd<T, ChannelDetector<N>> {
+    /// Check if the Cd has been changed since `channel` was last reset (or created.)
+    ///
+    /// # Panics
+    /// If `channel >= N`.
+    ///
+    /// ```
+    /// use changed::{Cd, ChannelDetector};
+    /// let mut cd = Cd::with_detector(5, ChannelDetector::<2>::new());
+    /// assert!(!cd.changed_in(0));
+    /// *cd += 1;
+    /// assert!(cd.changed_in(0));
+    /// assert!(cd.changed_in(1));
+    /// ```
+    pub fn changed_in(&self, channel: usize) -> bool {
+        self.detector.is_set(channel)
+    }
+
+    /// Reset the change tracking of `channel` to false, leaving the other channels alone.
+    ///
+    /// # Panics
+    /// If `channel >= N`.
+    ///
+    /// ```
+    /// use changed::{Cd, ChannelDetector};
+    /// let mut cd = Cd::with_detector_true(5, ChannelDetector::<2>::new());
+    /// cd.reset_channel(0);
+    /// assert!(!cd.changed_in(0));
+    /// assert!(cd.changed_in(1));
+    /// cd.reset_channel(1);
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn reset_channel(&mut self, channel: usize) {
+        self.detector.clear(channel);
+    }
+}
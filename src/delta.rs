@@ -0,0 +1,270 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::{Cd, CdMap, CdSet, CdVec, ChangeDetector, ChangeKind, MapBackend, SetBackend};
+
+/// A tracker that can describe what changed since the last reset, to send it elsewhere.
+///
+/// [`serde_delta()`](Delta::serde_delta()) is `None` when nothing changed, and otherwise only holds
+/// the changed parts: the changed fields of a `#[derive(Tracked)]` struct with
+/// `#[tracked(delta)]`, or the changed entries of a [`CdVec`], [`CdMap`] or [`CdSet`].
+/// [`apply_delta()`](Delta::apply_delta()) patches a receiving tracker in place,
+/// tripping its change detection for what was patched.
+///
+/// ```
+/// use changed::{CdMap, Delta};
+///
+/// let mut sender: CdMap<String, i32> = CdMap::default();
+/// let mut receiver: CdMap<String, i32> = CdMap::default();
+/// for cd in [&mut sender, &mut receiver] {
+///     cd.mutate_silently().insert("hp".to_string(), 10);
+///     cd.mutate_silently().insert("mp".to_string(), 5);
+/// }
+///
+/// *sender.get_mut("hp").unwrap() -= 3;
+/// let json = serde_json::to_string(&sender.serde_delta()).unwrap();
+/// assert_eq!(json, r#"{"upserts":[["hp",7]],"removed":[]}"#);
+///
+/// receiver.apply_delta(serde_json::from_str(&json).unwrap());
+/// assert_eq!(receiver["hp"], 7);
+/// assert!(receiver.changed());
+/// ```
+pub trait Delta {
+    /// The changes, in a form that can be sent.
+    type Delta: Serialize + DeserializeOwned;
+
+    /// What changed since the last reset, or `None` if nothing did.
+    ///
+    /// Not to be confused with [`Cd::delta()`], the difference from the baseline of a detector.
+    fn serde_delta(&self) -> Option<Self::Delta>;
+
+    /// Apply changes from [`serde_delta()`](Delta::serde_delta()) on another tracker.
+    fn apply_delta(&mut self, delta: Self::Delta);
+}
+
+/// The delta of a `Cd` is the whole data.
+/// ```
+/// use changed::{Cd, CdPrevious, Delta, NewCd};
+/// let mut cd = Cd::new(5);
+/// assert_eq!(cd.serde_delta(), None);
+/// *cd += 1;
+/// assert_eq!(cd.serde_delta(), Some(6));
+///
+/// let mut previous = CdPrevious::new(1);
+/// *previous = 3;
+/// assert_eq!(previous.serde_delta(), Some(3));
+/// assert_eq!(previous.delta(), 2);
+/// ```
+impl<T, D> Delta for Cd<T, D>
+where
+    T: Clone + Serialize + DeserializeOwned,
+    D: ChangeDetector<T>,
+{
+    type Delta = T;
+
+    fn serde_delta(&self) -> Option<T> {
+        if self.changed() {
+            Some(self.data.clone())
+        } else {
+            None
+        }
+    }
+
+    fn apply_delta(&mut self, delta: T) {
+        **self = delta;
+    }
+}
+
+/// The delta of a [`CdVec`]: its length and its dirty elements.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VecDelta<T> {
+    /// The length of the vector.
+    pub len: usize,
+    /// The dirty elements and their indices, in order.
+    pub entries: Vec<(usize, T)>,
+}
+
+/// Indices past the end of the receiver are pushed, so they should be in order.
+/// Dirty indices past the end of the sender are left out.
+/// ```
+/// use changed::{CdVec, Delta};
+/// let mut sender = CdVec::new(vec![1, 2, 3]);
+/// let mut receiver = CdVec::new(vec![1, 2, 3]);
+/// sender[0] = 10;
+/// sender.pop();
+/// sender.push(30);
+/// sender.push(40);
+/// receiver.apply_delta(sender.serde_delta().unwrap());
+/// assert_eq!(*receiver, [10, 2, 30, 40]);
+/// ```
+impl<T> Delta for CdVec<T>
+where
+    T: Clone + Serialize + DeserializeOwned,
+{
+    type Delta = VecDelta<T>;
+
+    fn serde_delta(&self) -> Option<VecDelta<T>> {
+        if !self.changed() {
+            return None;
+        }
+        let entries = self
+            .dirty_ranges()
+            .iter()
+            .flat_map(|range| range.clone())
+            // Dirty indices can be past the end after a silent truncate.
+            .map_while(|i| Some((i, self.get(i)?.clone())))
+            .collect();
+        Some(VecDelta {
+            len: self.len(),
+            entries,
+        })
+    }
+
+    fn apply_delta(&mut self, delta: VecDelta<T>) {
+        if delta.len < self.len() {
+            self.truncate(delta.len);
+        }
+        for (i, value) in delta.entries {
+            if i < self.len() {
+                self[i] = value;
+            } else {
+                self.push(value);
+            }
+        }
+    }
+}
+
+/// The delta of a [`CdMap`]: the inserted or updated entries, and the removed keys.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapDelta<K, V> {
+    /// Entries that were inserted or updated.
+    pub upserts: Vec<(K, V)>,
+    /// Keys that were removed.
+    pub removed: Vec<K>,
+}
+
+/// Changed keys that are no longer in the map, after a silent remove, are sent as removed.
+impl<K, V, M> Delta for CdMap<K, V, M>
+where
+    K: Clone + Serialize + DeserializeOwned,
+    V: Clone + Serialize + DeserializeOwned,
+    M: MapBackend<K, V>,
+{
+    type Delta = MapDelta<K, V>;
+
+    fn serde_delta(&self) -> Option<MapDelta<K, V>> {
+        if !self.changed() {
+            return None;
+        }
+        let mut delta = MapDelta {
+            upserts: Vec::new(),
+            removed: Vec::new(),
+        };
+        for (key, kind) in self.changes() {
+            match kind {
+                ChangeKind::Removed => delta.removed.push(key.clone()),
+                // The key can be gone after a silent remove.
+                ChangeKind::Inserted | ChangeKind::Updated => match self.get(key) {
+                    Some(value) => delta.upserts.push((key.clone(), value.clone())),
+                    None => delta.removed.push(key.clone()),
+                },
+            }
+        }
+        Some(delta)
+    }
+
+    fn apply_delta(&mut self, delta: MapDelta<K, V>) {
+        for key in &delta.removed {
+            self.remove(key);
+        }
+        for (key, value) in delta.upserts {
+            self.insert(key, value);
+        }
+    }
+}
+
+/// The delta of a [`CdSet`]: the added and removed elements.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SetDelta<T> {
+    /// Elements that were added.
+    pub added: Vec<T>,
+    /// Elements that were removed.
+    pub removed: Vec<T>,
+}
+
+/// ```
+/// use changed::{CdSet, Delta};
+/// use std::collections::BTreeSet;
+/// let mut sender: CdSet<u32, BTreeSet<_>> = CdSet::new(BTreeSet::from([1, 2]));
+/// let mut receiver = CdSet::new(BTreeSet::from([1, 2]));
+/// sender.insert(3);
+/// sender.remove(&1);
+/// receiver.apply_delta(sender.serde_delta().unwrap());
+/// assert_eq!(*receiver, BTreeSet::from([2, 3]));
+/// ```
+impl<T, S> Delta for CdSet<T, S>
+where
+    T: Clone + Serialize + DeserializeOwned,
+    S: SetBackend<T>,
+{
+    type Delta = SetDelta<T>;
+
+    fn serde_delta(&self) -> Option<SetDelta<T>> {
+        if !self.changed() {
+            return None;
+        }
+        Some(SetDelta {
+            added: self.added().iter().cloned().collect(),
+            removed: self.removed().iter().cloned().collect(),
+        })
+    }
+
+    fn apply_delta(&mut self, delta: SetDelta<T>) {
+        for value in &delta.removed {
+            self.remove(value);
+        }
+        for value in delta.added {
+            self.insert(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CdMap, CdVec, Delta};
+
+    #[test]
+    fn vec_deltas_replay_structural_changes() {
+        let original = vec![1, 2, 3, 4, 5];
+        let mut sender = CdVec::new(original.clone());
+        let mut receiver = CdVec::new(original);
+
+        sender.remove(1);
+        sender.truncate(2);
+        sender.insert(0, 0);
+        sender.extend([7, 8, 9]);
+        sender.swap_remove(4);
+
+        let json = serde_json::to_string(&sender.serde_delta()).unwrap();
+        receiver.apply_delta(serde_json::from_str::<Option<_>>(&json).unwrap().unwrap());
+        assert_eq!(*receiver, *sender);
+
+        sender.reset();
+        assert_eq!(sender.serde_delta(), None);
+    }
+
+    #[test]
+    fn silent_removals_do_not_panic() {
+        let mut vec = CdVec::new(vec![1, 2, 3]);
+        vec[2] = 4;
+        vec.mutate_silently().truncate(1);
+        let delta = vec.serde_delta().unwrap();
+        assert_eq!((delta.len, delta.entries), (1, Vec::new()));
+
+        let mut map: CdMap<u8, u8> = CdMap::default();
+        map.insert(1, 1);
+        map.mutate_silently().remove(&1);
+        let delta = map.serde_delta().unwrap();
+        assert_eq!((delta.upserts, delta.removed), (Vec::new(), vec![1]));
+    }
+}
@@ -0,0 +1,290 @@
+use std::ops::Sub;
+
+use crate::Cd;
+
+/// The strategy a [`Cd`](crate::Cd) uses to decide whether its data has changed.
+///
+/// `Cd` calls into its detector from `deref_mut()`, [`changed()`](crate::Cd::changed())
+/// and [`reset()`](crate::Cd::reset()). The crate ships [`FlagDetector`] (the default),
+/// [`GenerationDetector`], [`EqDetector`](crate::EqDetector), [`HashDetector`](crate::HashDetector),
+/// [`PreviousDetector`](crate::PreviousDetector) and [`ApproxDetector`](crate::ApproxDetector),
+/// but any type can implement it:
+///
+/// ```
+/// use changed::{Cd, ChangeDetector};
+///
+/// // Only report changes bigger than 0.5
+/// struct Epsilon { baseline: f64, forced: bool }
+///
+/// impl ChangeDetector<f64> for Epsilon {
+///     fn on_mutate(&mut self, _data: &f64) {}
+///     fn is_changed(&self, data: &f64) -> bool {
+///         self.forced || (data - self.baseline).abs() > 0.5
+///     }
+///     fn on_reset(&mut self, data: &f64) {
+///         self.baseline = *data;
+///         self.forced = false;
+///     }
+///     fn mark_changed(&mut self) {
+///         self.forced = true;
+///     }
+/// }
+///
+/// let mut cd = Cd::with_detector(1.0, Epsilon { baseline: 0.0, forced: false });
+/// *cd += 0.25;
+/// assert!(!cd.changed());
+/// *cd += 0.5;
+/// assert!(cd.changed());
+/// ```
+pub trait ChangeDetector<T> {
+    /// Called by `deref_mut()`, before the data is handed out.
+    fn on_mutate(&mut self, data: &T);
+
+    /// Whether `data` counts as changed since the last reset.
+    fn is_changed(&self, data: &T) -> bool;
+
+    /// Called when the `Cd` is created and on every reset, with the data as it is now.
+    fn on_reset(&mut self, data: &T);
+
+    /// Report changed until the next reset, whatever the data is.
+    fn mark_changed(&mut self);
+}
+
+/// A [`ChangeDetector`] that keeps the data as it was at the last reset.
+pub trait Baseline<T>: ChangeDetector<T> {
+    /// The data as of the last reset. `data` is the current data, for detectors
+    /// that only keep a copy once it has been mutated.
+    fn baseline<'a>(&'a self, data: &'a T) -> &'a T;
+}
+
+/// `new()` and `new_true()` for a [`Cd`] whose detector needs no configuration,
+/// such as [`CdEq`](crate::CdEq), [`CdHash`](crate::CdHash) or [`CdPrevious`](crate::CdPrevious).
+///
+/// This is a trait because `Cd::new()` for the default [`FlagDetector`] is inherent,
+/// and a second inherent `new()` would make `Cd::new(5)` ambiguous.
+///
+/// ```
+/// use changed::{CdEq, NewCd};
+/// let mut cd = CdEq::new(5);
+/// *cd = 5;
+/// assert!(!cd.changed());
+/// assert!(CdEq::new_true(5).changed());
+/// ```
+pub trait NewCd<T>: Sized {
+    /// Create a new Cd with data, using the default detector.
+    /// It is initialized to false for change detection.
+    fn new(data: T) -> Self;
+
+    /// Create a new Cd with data, using the default detector.
+    /// It is initialized to true for change detection.
+    fn new_true(data: T) -> Self;
+}
+
+impl<T, D: ChangeDetector<T> + Default> NewCd<T> for Cd<T, D> {
+    fn new(data: T) -> Self {
+        Cd::with_detector(data, D::default())
+    }
+
+    fn new_true(data: T) -> Self {
+        Cd::with_detector_true(data, D::default())
+    }
+}
+
+/// The default detector: a `bool` that is set by every `deref_mut()`.
+///
+/// ```
+/// use changed::Cd;
+/// let mut cd = Cd::new(5);
+/// *cd = 5;
+/// // Any deref_mut is a change, even if the value is the same.
+/// assert!(cd.changed());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct FlagDetector {
+    changed: bool,
+}
+
+impl<T> ChangeDetector<T> for FlagDetector {
+    fn on_mutate(&mut self, _data: &T) {
+        self.changed = true;
+    }
+
+    fn is_changed(&self, _data: &T) -> bool {
+        self.changed
+    }
+
+    fn on_reset(&mut self, _data: &T) {
+        self.changed = false;
+    }
+
+    fn mark_changed(&mut self) {
+        self.changed = true;
+    }
+}
+
+/// A change tick recorded by [`GenerationDetector`].
+///
+/// Ticks wrap around, so they are only compared with [`is_newer_than()`](Tick::is_newer_than()),
+/// which stays correct as long as the two ticks are less than `u32::MAX / 2` mutations apart.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tick(u32);
+
+impl Tick {
+    /// Create a tick from its raw value.
+    pub fn new(tick: u32) -> Tick {
+        Tick(tick)
+    }
+
+    /// The raw value of the tick.
+    pub fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Whether this tick comes after `other`, accounting for wraparound.
+    /// ```
+    /// use changed::Tick;
+    /// assert!(Tick::new(2).is_newer_than(Tick::new(1)));
+    /// assert!(!Tick::new(1).is_newer_than(Tick::new(1)));
+    /// assert!(Tick::new(0).is_newer_than(Tick::new(u32::MAX)));
+    /// ```
+    pub fn is_newer_than(self, other: Tick) -> bool {
+        let ahead = self.0.wrapping_sub(other.0);
+        ahead != 0 && ahead <= u32::MAX / 2
+    }
+
+    fn next(self) -> Tick {
+        Tick(self.0.wrapping_add(1))
+    }
+}
+
+/// Records a change tick on every `deref_mut()`.
+///
+/// A plain flag can only be reset by one consumer. With ticks, every consumer keeps its
+/// own cursor and asks [`changed_since()`](crate::Cd::changed_since()) instead,
+/// while [`changed()`](crate::Cd::changed()) and [`reset()`](crate::Cd::reset()) work as usual.
+///
+/// ```
+/// use changed::{Cd, GenerationDetector};
+/// let mut cd = Cd::with_detector(5, GenerationDetector::default());
+/// let renderer = cd.tick();
+/// let network = cd.tick();
+///
+/// *cd += 1;
+/// assert!(cd.changed_since(renderer));
+/// let renderer = cd.tick();
+///
+/// // The network still sees the change after the renderer caught up.
+/// assert!(!cd.changed_since(renderer));
+/// assert!(cd.changed_since(network));
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct GenerationDetector {
+    tick: Tick,
+    reset_tick: Tick,
+}
+
+impl GenerationDetector {
+    /// The tick of the latest mutation.
+    pub fn tick(&self) -> Tick {
+        self.tick
+    }
+}
+
+impl<T> ChangeDetector<T> for GenerationDetector {
+    fn on_mutate(&mut self, _data: &T) {
+        self.tick = self.tick.next();
+    }
+
+    fn is_changed(&self, _data: &T) -> bool {
+        self.tick != self.reset_tick
+    }
+
+    fn on_reset(&mut self, _data: &T) {
+        self.reset_tick = self.tick;
+    }
+
+    fn mark_changed(&mut self) {
+        self.tick = self.tick.next();
+    }
+}
+
+impl<T, D: Baseline<T>> Cd<T, D> {
+    /// The data as of the last reset (or creation.)
+    /// ```
+    /// use changed::{Cd, EqDetector};
+    /// let mut cd = Cd::with_detector(5, EqDetector::new());
+    /// *cd = 10;
+    /// assert_eq!(*cd.baseline(), 5);
+    /// cd.reset();
+    /// assert_eq!(*cd.baseline(), 10);
+    /// ```
+    pub fn baseline(&self) -> &T {
+        self.detector.baseline(&self.data)
+    }
+
+    /// How far the data moved since the last reset: the data minus its [`baseline()`](Cd::baseline()).
+    /// ```
+    /// use changed::{Cd, EqDetector};
+    /// let mut cd = Cd::with_detector(5, EqDetector::new());
+    /// *cd = 8;
+    /// assert_eq!(cd.delta(), 3);
+    /// ```
+    pub fn delta(&self) -> <T as Sub>::Output
+    where
+        T: Clone + Sub,
+    {
+        self.data.clone() - self.baseline().clone()
+    }
+}
+
+impl<T> Cd<T, GenerationDetector> {
+    /// The tick of the latest mutation. Store it as a cursor for [`changed_since()`](Cd::changed_since()).
+    /// ```
+    /// use changed::{Cd, GenerationDetector};
+    /// let mut cd = Cd::with_detector(5, GenerationDetector::default());
+    /// let before = cd.tick();
+    /// *cd += 1;
+    /// assert!(cd.tick().is_newer_than(before));
+    /// ```
+    pub fn tick(&self) -> Tick {
+        self.detector.tick()
+    }
+
+    /// Check if the Cd has been changed after `last_seen`. Not affected by reset.
+    /// ```
+    /// use changed::{Cd, GenerationDetector};
+    /// let mut cd = Cd::with_detector(5, GenerationDetector::default());
+    /// let seen = cd.tick();
+    /// *cd += 1;
+    /// cd.reset();
+    /// assert!(cd.changed_since(seen));
+    /// ```
+    pub fn changed_since(&self, last_seen: Tick) -> bool {
+        self.detector.tick().is_newer_than(last_seen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cd, GenerationDetector, Tick};
+
+    #[test]
+    fn ticks_wrap_around() {
+        let detector = GenerationDetector {
+            tick: Tick::new(u32::MAX - 1),
+            reset_tick: Tick::new(u32::MAX - 1),
+        };
+        let mut cd = Cd::with_detector(0, detector);
+        let seen = cd.tick();
+        *cd += 1;
+        *cd += 1;
+        *cd += 1;
+        assert_eq!(cd.tick(), Tick::new(1));
+        assert!(cd.changed());
+        assert!(cd.changed_since(seen));
+        assert!(!seen.is_newer_than(cd.tick()));
+        cd.reset();
+        assert!(!cd.changed());
+        assert!(!cd.changed_since(cd.tick()));
+    }
+}
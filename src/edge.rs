@@ -0,0 +1,59 @@
+use crate::{Baseline, Cd};
+
+/// Edges of a `bool`, relative to the last reset. Needs a [`Baseline`] detector,
+/// such as [`PreviousDetector`](crate::PreviousDetector) or [`EqDetector`](crate::EqDetector).
+impl<D: Baseline<bool>> Cd<bool, D> {
+    /// Whether the data went from false to true since the last reset, like a key that was just pressed.
+    /// ```
+    /// use changed::{Cd, PreviousDetector};
+    /// let mut pressed = Cd::with_detector(false, PreviousDetector::new());
+    /// *pressed = true;
+    /// assert!(pressed.rose());
+    /// pressed.reset();
+    /// assert!(!pressed.rose());
+    /// ```
+    pub fn rose(&self) -> bool {
+        !*self.baseline() && **self
+    }
+
+    /// Whether the data went from true to false since the last reset, like a key that was just released.
+    /// ```
+    /// use changed::{Cd, PreviousDetector};
+    /// let mut pressed = Cd::with_detector(true, PreviousDetector::new());
+    /// *pressed = false;
+    /// assert!(pressed.fell());
+    /// assert!(!pressed.rose());
+    /// ```
+    pub fn fell(&self) -> bool {
+        *self.baseline() && !**self
+    }
+}
+
+/// Transitions between values, such as the states of a state machine, relative to the last reset.
+/// Needs a [`Baseline`] detector.
+impl<T: PartialEq, D: Baseline<T>> Cd<T, D> {
+    /// Whether the data is `value` now but was not at the last reset.
+    /// ```
+    /// use changed::{Cd, EqDetector};
+    ///
+    /// #[derive(Clone, PartialEq)]
+    /// enum State { Idle, Running }
+    ///
+    /// let mut state = Cd::with_detector(State::Idle, EqDetector::new());
+    /// *state = State::Running;
+    /// assert!(state.entered(&State::Running));
+    /// assert!(state.exited(&State::Idle));
+    ///
+    /// // Leaving and coming back before a reset is not a transition.
+    /// *state = State::Idle;
+    /// assert!(!state.exited(&State::Idle));
+    /// ```
+    pub fn entered(&self, value: &T) -> bool {
+        **self == *value && *self.baseline() != *value
+    }
+
+    /// Whether the data was `value` at the last reset but is not now.
+    pub fn exited(&self, value: &T) -> bool {
+        *self.baseline() == *value && **self != *value
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{Baseline, Cd, ChangeDetector};
+
+/// CdEq: Change Detection by Equality
+///
+/// Unlike a plain [`Cd`], `CdEq` keeps a snapshot of the data taken at creation
+/// and [`reset()`](Cd::reset()), and [`changed()`](Cd::changed())
+/// compares the current data against it. Writing the same value back is not a change.
+///
+/// ```
+/// use changed::{CdEq, NewCd};
+/// let mut cd = CdEq::new(5);
+/// *cd = 5;
+/// assert!(!cd.changed());
+/// *cd = 6;
+/// assert!(cd.changed());
+/// ```
+pub type CdEq<T> = Cd<T, EqDetector<T>>;
+
+/// Compares the data against a clone taken at the last reset.
+#[derive(Debug, Clone)]
+pub struct EqDetector<T> {
+    baseline: Option<T>,
+    forced: bool,
+}
+
+impl<T> EqDetector<T> {
+    /// Create an EqDetector. The baseline is taken when it is given to a [`Cd`].
+    pub fn new() -> EqDetector<T> {
+        EqDetector {
+            baseline: None,
+            forced: false,
+        }
+    }
+
+    /// The snapshot taken at the last reset, if there has been one.
+    pub fn baseline(&self) -> Option<&T> {
+        self.baseline.as_ref()
+    }
+}
+
+impl<T> Default for EqDetector<T> {
+    fn default() -> Self {
+        EqDetector::new()
+    }
+}
+
+impl<T: PartialEq + Clone> ChangeDetector<T> for EqDetector<T> {
+    fn on_mutate(&mut self, _data: &T) {}
+
+    fn is_changed(&self, data: &T) -> bool {
+        self.forced || self.baseline.as_ref() != Some(data)
+    }
+
+    fn on_reset(&mut self, data: &T) {
+        match &mut self.baseline {
+            Some(baseline) => baseline.clone_from(data),
+            None => self.baseline = Some(data.clone()),
+        }
+        self.forced = false;
+    }
+
+    fn mark_changed(&mut self) {
+        self.forced = true;
+    }
+}
+
+impl<T: PartialEq + Clone> Baseline<T> for EqDetector<T> {
+    fn baseline<'a>(&'a self, data: &'a T) -> &'a T {
+        self.baseline.as_ref().unwrap_or(data)
+    }
+}
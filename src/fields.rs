@@ -0,0 +1,38 @@
+/// A struct with a per-field change tracker, usually from `#[derive(Tracked)]`.
+///
+/// The derive generates, for a struct `Player`:
+/// - `PlayerTracked`, which keeps every field in a [`Cd`](crate::Cd), with accessors
+///   `field()`, `field_mut()` (trips change detection), `field_mut_silently()`,
+///   `field_changed()` and `reset_field()` for each field, and
+///   `changed_fields()`, `changed()`, `reset_all()` and `into_inner()` for the whole struct.
+/// - `PlayerField`, an enum with a variant for each field, yielded by `changed_fields()`.
+///
+/// `PlayerTracked` also implements [`AnyChanged`](crate::AnyChanged) and [`CheckChanged`](crate::CheckChanged).
+///
+/// Fields marked `#[tracked(nested)]` hold their own tracker instead of a `Cd`,
+/// so their type has to be `Trackable` too.
+///
+/// With the `serde` feature, `#[tracked(delta)]` on the struct also generates `PlayerDelta`,
+/// holding only the changed fields, and implements `Delta` for the tracker.
+pub trait Trackable: Sized {
+    /// The tracker for this struct.
+    type Tracked: TrackedFields<Value = Self>;
+
+    /// Start tracking changes. Every field is initialized to false for change detection.
+    fn track(self) -> Self::Tracked;
+}
+
+/// The tracker of a [`Trackable`] struct.
+pub trait TrackedFields {
+    /// The struct being tracked.
+    type Value;
+
+    /// Check if any field has been changed since the last call to reset (or created.)
+    fn changed(&self) -> bool;
+
+    /// Reset the change tracking of every field to false.
+    fn reset_all(&mut self);
+
+    /// Stop tracking changes and return the struct.
+    fn into_inner(self) -> Self::Value;
+}
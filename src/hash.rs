@@ -0,0 +1,116 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash};
+
+use crate::{Cd, ChangeDetector};
+
+/// CdHash: Change Detection by Hash
+///
+/// For data that is too expensive to clone for [`CdEq`](crate::CdEq).
+/// A `u64` hash of the data is stored at creation and [`reset()`](Cd::reset()).
+/// `deref_mut()` sets a dirty flag like a plain [`Cd`], and [`changed()`](Cd::changed())
+/// only rehashes the data when that flag is set.
+///
+/// ```
+/// use changed::{CdHash, NewCd};
+/// let mut cd: CdHash<Vec<i32>> = CdHash::new(vec![1, 2, 3]);
+/// cd.push(4);
+/// cd.pop();
+/// assert!(!cd.changed());
+/// cd.push(5);
+/// assert!(cd.changed());
+/// ```
+pub type CdHash<T, S = BuildHasherDefault<DefaultHasher>> = Cd<T, HashDetector<S>>;
+
+/// Compares a hash of the data against the hash taken at the last reset,
+/// but only once `deref_mut()` has been called.
+///
+/// The hasher can be swapped out with [`with_hasher()`](HashDetector::with_hasher()).
+#[derive(Debug, Clone)]
+pub struct HashDetector<S = BuildHasherDefault<DefaultHasher>> {
+    hasher: S,
+    hash: Option<u64>,
+    dirty: bool,
+    forced: bool,
+}
+
+impl HashDetector {
+    /// Create a HashDetector using the default hasher.
+    pub fn new() -> HashDetector {
+        HashDetector::with_hasher(Default::default())
+    }
+}
+
+impl<S> HashDetector<S> {
+    /// Create a HashDetector using `hasher`.
+    ///
+    /// ```
+    /// use changed::{Cd, HashDetector};
+    /// use std::collections::hash_map::RandomState;
+    /// let cd = Cd::with_detector(5, HashDetector::with_hasher(RandomState::new()));
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn with_hasher(hasher: S) -> HashDetector<S> {
+        HashDetector {
+            hasher,
+            hash: None,
+            dirty: false,
+            forced: false,
+        }
+    }
+}
+
+impl<T: Hash, S: BuildHasher> Cd<T, HashDetector<S>> {
+    /// Create a new CdHash with data, hashing it with `hasher`.
+    /// It is initialized to false for change detection.
+    /// ```
+    /// use changed::CdHash;
+    /// use std::collections::hash_map::RandomState;
+    /// let cd = CdHash::with_hasher(5, RandomState::new());
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn with_hasher(data: T, hasher: S) -> Self {
+        Cd::with_detector(data, HashDetector::with_hasher(hasher))
+    }
+}
+
+impl<S: Default> Default for HashDetector<S> {
+    fn default() -> Self {
+        HashDetector::with_hasher(S::default())
+    }
+}
+
+impl<T: Hash, S: BuildHasher> ChangeDetector<T> for HashDetector<S> {
+    fn on_mutate(&mut self, _data: &T) {
+        self.dirty = true;
+    }
+
+    fn is_changed(&self, data: &T) -> bool {
+        self.forced || (self.dirty && Some(self.hasher.hash_one(data)) != self.hash)
+    }
+
+    fn on_reset(&mut self, data: &T) {
+        // Always rehash: the data may have been mutated silently.
+        self.hash = Some(self.hasher.hash_one(data));
+        self.dirty = false;
+        self.forced = false;
+    }
+
+    fn mark_changed(&mut self) {
+        self.forced = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CdHash, NewCd};
+
+    #[test]
+    fn reset_rehashes_after_silent_mutations() {
+        let mut cd: CdHash<Vec<i32>> = CdHash::new(vec![1]);
+        cd.mutate_silently().push(2);
+        cd.reset();
+        cd.push(3);
+        cd.pop();
+        assert!(!cd.changed());
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+
+use crate::Cd;
+
+/// CdHistory: Change Detection with undo and redo
+///
+/// Every [`reset()`](CdHistory::reset()) or [`commit()`](CdHistory::commit()) after a change
+/// records a snapshot. [`undo()`](CdHistory::undo()) and [`redo()`](CdHistory::redo())
+/// move between snapshots and, unlike `mutate_silently()`, trip change detection,
+/// so anything that depends on the value refreshes.
+///
+/// Committing a change after an undo drops the snapshots that could have been redone.
+///
+/// ```
+/// use changed::CdHistory;
+///
+/// let mut doc = CdHistory::new(String::from("a"));
+/// doc.push('b');
+/// doc.reset();
+/// doc.push('c');
+/// doc.reset();
+///
+/// assert!(doc.undo());
+/// assert_eq!(*doc, "ab");
+/// assert!(doc.changed());
+///
+/// assert!(doc.redo());
+/// assert_eq!(*doc, "abc");
+/// ```
+pub struct CdHistory<T> {
+    cd: Cd<T>,
+    /// The data as of the last commit.
+    committed: T,
+    past: VecDeque<T>,
+    future: Vec<T>,
+    limit: usize,
+    uncommitted: bool,
+}
+
+impl<T: Clone> CdHistory<T> {
+    /// Create a new CdHistory with data, keeping every snapshot.
+    /// It is initialized to false for change detection.
+    ///
+    /// ```
+    /// use changed::CdHistory;
+    /// let cd = CdHistory::new(5);
+    /// assert!(!cd.changed());
+    /// assert!(!cd.can_undo());
+    /// ```
+    pub fn new(data: T) -> CdHistory<T> {
+        CdHistory::with_limit(data, usize::MAX)
+    }
+
+    /// Create a new CdHistory with data, keeping at most `limit` snapshots to undo to.
+    /// It is initialized to false for change detection.
+    ///
+    /// ```
+    /// use changed::CdHistory;
+    /// let mut cd = CdHistory::with_limit(0, 1);
+    /// for i in 1..=3 {
+    ///     *cd = i;
+    ///     cd.commit();
+    /// }
+    /// assert!(cd.undo());
+    /// assert!(!cd.undo());
+    /// assert_eq!(*cd, 2);
+    /// ```
+    pub fn with_limit(data: T, limit: usize) -> CdHistory<T> {
+        CdHistory {
+            committed: data.clone(),
+            cd: Cd::new(data),
+            past: VecDeque::new(),
+            future: Vec::new(),
+            limit,
+            uncommitted: false,
+        }
+    }
+
+    /// Record a snapshot if the data was mutated since the last commit.
+    /// Does not reset change tracking.
+    ///
+    /// ```
+    /// use changed::CdHistory;
+    /// let mut cd = CdHistory::new(5);
+    /// *cd += 1;
+    /// cd.commit();
+    /// assert!(cd.changed());
+    /// assert!(cd.can_undo());
+    /// ```
+    pub fn commit(&mut self) {
+        if !self.uncommitted {
+            return;
+        }
+        self.uncommitted = false;
+        let previous = std::mem::replace(&mut self.committed, (*self.cd).clone());
+        self.future.clear();
+        if self.limit == 0 {
+            return;
+        }
+        if self.past.len() == self.limit {
+            self.past.pop_front();
+        }
+        self.past.push_back(previous);
+    }
+
+    /// Commit, then reset the change tracking to false.
+    /// ```
+    /// use changed::CdHistory;
+    /// let mut cd = CdHistory::new(5);
+    /// *cd += 1;
+    /// cd.reset();
+    /// assert!(!cd.changed());
+    /// assert!(cd.can_undo());
+    /// ```
+    pub fn reset(&mut self) {
+        self.commit();
+        self.cd.reset();
+    }
+
+    /// Go back to the previous snapshot, committing any pending change first so it can be redone.
+    /// Returns false if there is nothing to undo.
+    ///
+    /// ```
+    /// use changed::CdHistory;
+    /// let mut cd = CdHistory::new(5);
+    /// *cd += 1;
+    /// cd.reset();
+    /// assert!(cd.undo());
+    /// assert_eq!(*cd, 5);
+    /// assert!(cd.changed());
+    /// ```
+    pub fn undo(&mut self) -> bool {
+        self.commit();
+        match self.past.pop_back() {
+            Some(previous) => {
+                let current = std::mem::replace(&mut self.committed, previous);
+                self.future.push(current);
+                *self.cd = self.committed.clone();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Go forward to the snapshot that was last undone.
+    /// Returns false if there is nothing to redo.
+    ///
+    /// ```
+    /// use changed::CdHistory;
+    /// let mut cd = CdHistory::new(5);
+    /// *cd += 1;
+    /// cd.undo();
+    /// cd.reset();
+    /// assert!(cd.redo());
+    /// assert_eq!(*cd, 6);
+    /// assert!(cd.changed());
+    /// ```
+    pub fn redo(&mut self) -> bool {
+        self.commit();
+        match self.future.pop() {
+            Some(next) => {
+                let current = std::mem::replace(&mut self.committed, next);
+                self.past.push_back(current);
+                *self.cd = self.committed.clone();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Change how many snapshots to keep, dropping the oldest ones if there are too many.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        while self.past.len() > limit {
+            self.past.pop_front();
+        }
+    }
+}
+
+impl<T> CdHistory<T> {
+    /// Check if the CdHistory has been changed since the last call to reset (or created.)
+    /// ```
+    /// use changed::CdHistory;
+    /// let mut cd = CdHistory::new(5);
+    /// *cd += 5;
+    /// assert!(cd.changed());
+    /// ```
+    pub fn changed(&self) -> bool {
+        self.cd.changed()
+    }
+
+    /// Check if there is a snapshot to undo to, not counting uncommitted changes.
+    pub fn can_undo(&self) -> bool {
+        !self.past.is_empty()
+    }
+
+    /// Check if there is a snapshot to redo to.
+    pub fn can_redo(&self) -> bool {
+        !self.future.is_empty()
+    }
+
+    /// Take the data out of the CdHistory, dropping the history.
+    /// Consumes self and returns data.
+    /// ```
+    /// use changed::CdHistory;
+    /// let cd = CdHistory::new(5);
+    /// assert_eq!(cd.take(), 5);
+    /// ```
+    pub fn take(self) -> T {
+        self.cd.take()
+    }
+
+    /// Mutate the CdHistory without tripping change detection.
+    /// The mutation still ends up in the next snapshot.
+    ///
+    /// ```
+    /// use changed::CdHistory;
+    /// let mut cd = CdHistory::new(5);
+    /// *cd.mutate_silently() += 5;
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn mutate_silently(&mut self) -> &mut T {
+        self.uncommitted = true;
+        self.cd.mutate_silently()
+    }
+}
+
+/// deref does not trip change detection.
+/// ```
+/// use changed::CdHistory;
+/// let cd = CdHistory::new(5);
+/// assert_eq!(*cd, 5);
+/// assert!(!cd.changed());
+/// ```
+impl<T> Deref for CdHistory<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.cd
+    }
+}
+
+/// deref_mut trips change detection, and the change goes into the next snapshot.
+/// ```
+/// use changed::CdHistory;
+/// let mut cd = CdHistory::new(5);
+/// *cd += 5;
+/// assert!(cd.changed());
+/// ```
+impl<T> DerefMut for CdHistory<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.uncommitted = true;
+        &mut self.cd
+    }
+}
+
+/// Impl default where the data impls default. Change detection is initialized to false.
+/// ```
+/// use changed::CdHistory;
+/// let zero: CdHistory<i32> = CdHistory::default();
+/// assert!(!zero.changed());
+/// ```
+impl<T: Clone + Default> Default for CdHistory<T> {
+    fn default() -> Self {
+        CdHistory::new(T::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CdHistory;
+
+    #[test]
+    fn new_changes_drop_the_redo_branch() {
+        let mut cd = CdHistory::new(0);
+        for i in 1..=3 {
+            *cd = i;
+            cd.reset();
+        }
+        assert!(cd.undo());
+        assert!(cd.undo());
+        assert_eq!(*cd, 1);
+        assert!(cd.can_redo());
+
+        *cd = 10;
+        cd.reset();
+        assert!(!cd.can_redo());
+        assert!(!cd.redo());
+
+        assert!(cd.undo());
+        assert_eq!(*cd, 1);
+        assert!(cd.undo());
+        assert_eq!(*cd, 0);
+        assert!(!cd.undo());
+    }
+
+    #[test]
+    fn undo_commits_pending_changes() {
+        let mut cd = CdHistory::new(0);
+        *cd = 1;
+        cd.reset();
+        *cd = 2;
+        assert!(cd.undo());
+        assert_eq!(*cd, 1);
+        assert!(cd.redo());
+        assert_eq!(*cd, 2);
+    }
+
+    #[test]
+    fn a_zero_limit_keeps_no_history() {
+        let mut cd = CdHistory::with_limit(0, 0);
+        *cd = 1;
+        cd.reset();
+        assert!(!cd.undo());
+        assert_eq!(*cd, 1);
+    }
+}
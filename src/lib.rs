@@ -1,42 +1,138 @@
 //! `Cd`: A "smart pointer" that tracks changes to the data it owns.
-//! 
+//!
 //! ## Usage
 //! ```
 //! use changed::Cd;
-//! 
+//!
 //! // Create the change tracker with an i32
 //! let mut test: Cd<i32> = Cd::new(20);
-//! 
+//!
 //! // Mutate it (calling deref_mut through the *)
 //! *test += 5;
-//! 
+//!
 //! // changed() reports whether or not it was changed
 //! assert!(test.changed());
-//! 
+//!
 //! // Reset the tracker back to false
 //! test.reset();
-//! 
+//!
 //! // Read the data
 //! assert_eq!(*test, 25);
-//! 
+//!
 //! // That didn't trip the change detection!
 //! assert!(!test.changed());
 //! ```
-//! 
+//!
 //! ## How it works
 //! Technically, it doesn't track changes. It tracks calls to `deref_mut()`
 //! so it is entirely possible to call `deref_mut()` and not change it, giving a false positive.
-//! 
-//! Along with that, there is a function to mutate a `Cd` without tripping change detection. 
+//!
+//! Along with that, there is a function to mutate a `Cd` without tripping change detection.
+//!
+//! That is the default [`FlagDetector`]. The detection strategy is pluggable through the
+//! [`ChangeDetector`] trait: if false positives are a problem, [`CdEq`] compares the data
+//! against a snapshot taken at the last reset instead, and [`CdHash`] compares hashes
+//! for data that is too expensive to clone. [`CdApprox`] ignores changes within a tolerance,
+//! for floating point data. [`CdPrevious`] keeps the value from before
+//! the first mutation, for [`previous()`](Cd::previous()) and [`delta()`](Cd::delta()).
+//! With a detector that keeps a [`Baseline`], [`rose()`](Cd::rose()), [`fell()`](Cd::fell()), [`entered()`](Cd::entered())
+//! and [`exited()`](Cd::exited()) detect edges since the last reset.
+//!
+//! ## More trackers
+//! - [`CdChannels`] keeps a separate dirty flag per consumer.
+//! - [`SyncCd`] can be shared between threads, with a lock-free `changed()`.
+//! - [`CdWatch`] wakes async [`Watcher`]s when it changes.
+//! - [`CdObserved`] runs callbacks when it changes.
+//! - [`CdVec`] records which indices of a `Vec` changed.
+//! - [`CdMap`] records which keys of a `HashMap` or `BTreeMap` were inserted, updated or removed.
+//! - [`CdSet`] records which elements of a `HashSet` or `BTreeSet` were added or removed.
+//! - [`CdTree`] marks the ancestors of a changed node, so clean branches can be skipped.
+//! - [`CdHistory`] keeps snapshots to `undo()` and `redo()`.
+//! - [`query`] has a database of memoized queries, recomputed only when their inputs change.
+//! - [`reactive`] has signals, memos and effects that track what they read.
+//! - `#[derive(Tracked)]`, with the `derive` feature, tracks each field of a struct
+//!   separately. See [`Trackable`].
+//!
+//! [`Cd`], the trackers above, reactive [`Signal`](reactive::Signal)s and derived trackers
+//! implement [`AnyChanged`], as do tuples, arrays, `Vec`s and `Option`s of them,
+//! so `(a, b, c).changed()` checks a group at once, with [`CheckChanged`] for `&` references
+//! to them. A [`ChangeScope`] keeps weak handles to
+//! any number of them, to reset them all at the end of a frame.
+//!
+//! ## Features
+//! - `derive`: `#[derive(Tracked)]`.
+//! - `serde`: `Serialize` and `Deserialize` for [`Cd`], and `Delta` to send only what changed.
+//! - `json-patch`: RFC 6902 JSON Patches between the reset baseline and the current data.
 
 use std::ops::{Deref, DerefMut};
 
+mod any;
+mod approx;
+mod channel;
+#[cfg(feature = "serde")]
+mod delta;
+mod detector;
+mod edge;
+mod eq;
+mod fields;
+mod hash;
+mod history;
+mod map;
+mod observe;
+#[cfg(feature = "json-patch")]
+mod patch;
+mod previous;
+pub mod query;
+pub mod reactive;
+mod scope;
+#[cfg(feature = "serde")]
+mod ser;
+mod set;
+mod sync;
+mod tree;
+mod vec;
+mod watch;
+
+pub use any::{AnyChanged, CheckChanged};
+pub use approx::{Approx, ApproxDetector, CdApprox};
+pub use channel::{CdChannels, ChannelDetector};
+pub use detector::{Baseline, ChangeDetector, FlagDetector, GenerationDetector, NewCd, Tick};
+pub use eq::{CdEq, EqDetector};
+pub use fields::{Trackable, TrackedFields};
+pub use hash::{CdHash, HashDetector};
+pub use history::CdHistory;
+pub use map::{CdMap, ChangeKind, Entry, MapBackend, MapLookup};
+pub use observe::{CdObserved, ObserveGuard, Subscription};
+pub use previous::{CdPrevious, PreviousDetector};
+pub use scope::ChangeScope;
+pub use set::{CdSet, SetBackend, SetLookup};
+pub use sync::{SyncCd, SyncReadGuard, SyncWriteGuard};
+pub use tree::{CdTree, NodeId};
+pub use vec::CdVec;
+pub use watch::{CdWatch, WaitChanged, WatchClosed, WatchGuard, Watcher};
+
+#[cfg(feature = "derive")]
+pub use changed_derive::Tracked;
+#[cfg(feature = "serde")]
+pub use delta::{Delta, MapDelta, SetDelta, VecDelta};
+#[cfg(feature = "json-patch")]
+pub use patch::{PatchError, PatchOp};
+#[cfg(feature = "serde")]
+pub use ser::with_flag;
+
+// For `#[tracked(delta)]`.
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use serde as __serde;
+
 /// Cd: Change Detection
 ///
-/// Start by creating one with [`new()`](Cd::new()).
-pub struct Cd<T> {
+/// Start by creating one with [`new()`](Cd::new()),
+/// or [`with_detector()`](Cd::with_detector()) to pick a [`ChangeDetector`].
+/// The aliases for other detectors have a [`NewCd::new()`] too, like `CdEq::new(5)`.
+pub struct Cd<T, D = FlagDetector> {
     data: T,
-    changed: bool,
+    detector: D,
 }
 
 impl<T> Cd<T> {
@@ -48,10 +144,7 @@ impl<T> Cd<T> {
     /// let cd = Cd::new(5);
     /// ```
     pub fn new(data: T) -> Cd<T> {
-        Cd {
-            data,
-            changed: false,
-        }
+        Cd::with_detector(data, FlagDetector::default())
     }
 
     /// Create a new Cd with data.
@@ -62,10 +155,36 @@ impl<T> Cd<T> {
     /// assert!(cd.changed());
     /// ```
     pub fn new_true(data: T) -> Cd<T> {
-        Cd {
-            data,
-            changed: true,
-        }
+        Cd::with_detector_true(data, FlagDetector::default())
+    }
+}
+
+impl<T, D: ChangeDetector<T>> Cd<T, D> {
+    /// Create a new Cd with data, using detector for change detection.
+    /// It is initialized to false for change detection.
+    ///
+    /// ```
+    /// use changed::{Cd, EqDetector};
+    /// let cd = Cd::with_detector(5, EqDetector::new());
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn with_detector(data: T, mut detector: D) -> Cd<T, D> {
+        detector.on_reset(&data);
+        Cd { data, detector }
+    }
+
+    /// Create a new Cd with data, using detector for change detection.
+    /// It is initialized to true for change detection.
+    ///
+    /// ```
+    /// use changed::{Cd, EqDetector};
+    /// let cd = Cd::with_detector_true(5, EqDetector::new());
+    /// assert!(cd.changed());
+    /// ```
+    pub fn with_detector_true(data: T, detector: D) -> Cd<T, D> {
+        let mut cd = Cd::with_detector(data, detector);
+        cd.detector.mark_changed();
+        cd
     }
 
     /// Reset the change tracking to false.
@@ -76,7 +195,7 @@ impl<T> Cd<T> {
     /// assert!(!cd.changed());
     /// ```
     pub fn reset(&mut self) {
-        self.changed = false;
+        self.detector.on_reset(&self.data);
     }
 
     /// Take the data out of the Cd.
@@ -101,11 +220,11 @@ impl<T> Cd<T> {
     /// assert!(cd.changed());
     /// ```
     pub fn changed(&self) -> bool {
-        self.changed
+        self.detector.is_changed(&self.data)
     }
 
     /// Mutate the Cd without tripping change detection.
-    /// 
+    ///
     /// ```
     /// use changed::Cd;
     /// let mut cd = Cd::new(5);
@@ -115,6 +234,65 @@ impl<T> Cd<T> {
     pub fn mutate_silently(&mut self) -> &mut T {
         &mut self.data
     }
+
+    /// Replace the data, returning the old value. Trips change detection like `deref_mut()`.
+    /// ```
+    /// use changed::Cd;
+    /// let mut cd = Cd::new(5);
+    /// assert_eq!(cd.replace(10), 5);
+    /// assert_eq!(*cd, 10);
+    /// assert!(cd.changed());
+    /// ```
+    pub fn replace(&mut self, data: T) -> T {
+        std::mem::replace(&mut **self, data)
+    }
+
+    /// Mutate a copy of the data, and only write it back if `f` returns `Ok`.
+    ///
+    /// On `Err`, or if `f` panics, the data and the change tracking are left as they were.
+    /// Committing trips change detection like `deref_mut()`.
+    ///
+    /// ```
+    /// use changed::Cd;
+    /// let mut cd = Cd::new(vec![1, 2]);
+    ///
+    /// let failed: Result<(), &str> = cd.transaction(|v| {
+    ///     v.push(3);
+    ///     Err("too long")
+    /// });
+    /// assert!(failed.is_err());
+    /// assert_eq!(*cd, [1, 2]);
+    /// assert!(!cd.changed());
+    ///
+    /// let len = cd.transaction(|v| {
+    ///     v.push(3);
+    ///     Ok::<_, &str>(v.len())
+    /// });
+    /// assert_eq!(len, Ok(3));
+    /// assert_eq!(*cd, [1, 2, 3]);
+    /// assert!(cd.changed());
+    /// ```
+    pub fn transaction<R, E, F>(&mut self, f: F) -> Result<R, E>
+    where
+        T: Clone,
+        F: FnOnce(&mut T) -> Result<R, E>,
+    {
+        let mut working = self.data.clone();
+        let out = f(&mut working)?;
+        **self = working;
+        Ok(out)
+    }
+
+    /// The detector used for change detection.
+    /// ```
+    /// use changed::{Cd, GenerationDetector, Tick};
+    /// let mut cd = Cd::with_detector(5, GenerationDetector::default());
+    /// *cd += 5;
+    /// assert_eq!(cd.detector().tick(), Tick::new(1));
+    /// ```
+    pub fn detector(&self) -> &D {
+        &self.detector
+    }
 }
 
 /// deref does not trip change detection.
@@ -124,7 +302,7 @@ impl<T> Cd<T> {
 /// assert_eq!(*cd, 5); // deref for == 5
 /// assert!(!cd.changed()); // .changed() is false
 /// ```
-impl<T> Deref for Cd<T> {
+impl<T, D> Deref for Cd<T, D> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -140,37 +318,70 @@ impl<T> Deref for Cd<T> {
 /// assert_eq!(*cd, 10);
 /// assert!(cd.changed()); // .changed() is true
 /// ```
-impl<T> DerefMut for Cd<T> {
+impl<T, D: ChangeDetector<T>> DerefMut for Cd<T, D> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        self.changed = true;
+        self.detector.on_mutate(&self.data);
         &mut self.data
     }
 }
 
-/// Impl default where the data impls default. Change detection is initialized to false.
+/// Impl default where the data and detector impl default. Change detection is initialized to false.
 /// ```
 /// use changed::Cd;
 /// // 0 is default for i32.
 /// let zero: Cd<i32> = Cd::default();
 /// assert!(!zero.changed());
 /// ```
-impl<T: Default> Default for Cd<T> {
+impl<T: Default, D: ChangeDetector<T> + Default> Default for Cd<T, D> {
     fn default() -> Self {
-        Cd::new(T::default())
+        Cd::with_detector(T::default(), D::default())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Cd;
+    use crate::{Cd, EqDetector, GenerationDetector, HashDetector};
 
     #[test]
     fn it_works() {
         let mut changed = Cd::new(15);
         *changed += 5;
-        assert!(changed.changed);
+        assert!(changed.changed());
         changed.reset();
         assert_eq!(*changed, 20);
-        assert!(!changed.changed);
+        assert!(!changed.changed());
+    }
+
+    #[test]
+    fn detectors_agree_on_real_changes() {
+        let mut flag = Cd::new(1);
+        let mut generation = Cd::with_detector(1, GenerationDetector::default());
+        let mut eq = Cd::with_detector(1, EqDetector::new());
+        let mut hash = Cd::with_detector(1, HashDetector::new());
+        *flag += 1;
+        *generation += 1;
+        *eq += 1;
+        *hash += 1;
+        assert!(flag.changed() && generation.changed() && eq.changed() && hash.changed());
+        flag.reset();
+        generation.reset();
+        eq.reset();
+        hash.reset();
+        assert!(!flag.changed() && !generation.changed() && !eq.changed() && !hash.changed());
+    }
+
+    #[test]
+    fn transactions_roll_back_on_panic() {
+        let mut cd = Cd::new_true(vec![1]);
+        cd.reset();
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _: Result<(), ()> = cd.transaction(|v| {
+                v.push(2);
+                panic!("validation blew up");
+            });
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(*cd, [1]);
+        assert!(!cd.changed());
     }
 }
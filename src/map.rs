@@ -0,0 +1,453 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash};
+use std::ops::Deref;
+
+/// How a key of a [`CdMap`] changed since the last reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    /// The key was not in the map, and now it is.
+    Inserted,
+    /// The key was in the map, and its value may have changed.
+    Updated,
+    /// The key was in the map, and now it is not.
+    Removed,
+}
+
+impl ChangeKind {
+    /// What a change followed by `next` amounts to, or `None` if they cancel out.
+    fn then(self, next: ChangeKind) -> Option<ChangeKind> {
+        use ChangeKind::*;
+        match (self, next) {
+            (Inserted, Removed) => None,
+            (Inserted, _) => Some(Inserted),
+            (Removed, Inserted) => Some(Updated),
+            (_, next) => Some(next),
+        }
+    }
+}
+
+/// A map that can back a [`CdMap`]. Implemented for `HashMap` and `BTreeMap`.
+pub trait MapBackend<K, V>: Default + MapLookup<K, K, V> {
+    /// The same kind of map, used to record changes.
+    type ChangeLog: MapBackend<K, ChangeKind>;
+
+    /// See `HashMap::insert`.
+    fn insert(&mut self, key: K, value: V) -> Option<V>;
+    /// See `HashMap::iter`.
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a K, &'a V)> + 'a>;
+}
+
+/// Lookups in a [`MapBackend`] by a borrowed form `Q` of the key,
+/// like `HashMap::get` taking any `&Q` where `K: Borrow<Q>`.
+pub trait MapLookup<Q: ?Sized, K, V> {
+    /// See `HashMap::get`.
+    fn get(&self, key: &Q) -> Option<&V>;
+    /// See `HashMap::get_key_value`.
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)>;
+    /// See `HashMap::get_mut`.
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V>;
+    /// See `HashMap::remove_entry`.
+    fn remove_entry(&mut self, key: &Q) -> Option<(K, V)>;
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> MapBackend<K, V> for HashMap<K, V, S> {
+    type ChangeLog = HashMap<K, ChangeKind, S>;
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        HashMap::insert(self, key, value)
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a K, &'a V)> + 'a> {
+        Box::new(HashMap::iter(self))
+    }
+}
+
+impl<Q, K, V, S> MapLookup<Q, K, V> for HashMap<K, V, S>
+where
+    Q: Hash + Eq + ?Sized,
+    K: Hash + Eq + Borrow<Q>,
+    S: BuildHasher,
+{
+    fn get(&self, key: &Q) -> Option<&V> {
+        HashMap::get(self, key)
+    }
+
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        HashMap::get_key_value(self, key)
+    }
+
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        HashMap::get_mut(self, key)
+    }
+
+    fn remove_entry(&mut self, key: &Q) -> Option<(K, V)> {
+        HashMap::remove_entry(self, key)
+    }
+}
+
+impl<K: Ord, V> MapBackend<K, V> for BTreeMap<K, V> {
+    type ChangeLog = BTreeMap<K, ChangeKind>;
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        BTreeMap::insert(self, key, value)
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a K, &'a V)> + 'a> {
+        Box::new(BTreeMap::iter(self))
+    }
+}
+
+impl<Q, K, V> MapLookup<Q, K, V> for BTreeMap<K, V>
+where
+    Q: Ord + ?Sized,
+    K: Ord + Borrow<Q>,
+{
+    fn get(&self, key: &Q) -> Option<&V> {
+        BTreeMap::get(self, key)
+    }
+
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        BTreeMap::get_key_value(self, key)
+    }
+
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        BTreeMap::get_mut(self, key)
+    }
+
+    fn remove_entry(&mut self, key: &Q) -> Option<(K, V)> {
+        BTreeMap::remove_entry(self, key)
+    }
+}
+
+/// CdMap: Change Detection for each key of a map
+///
+/// Records which keys were [`Inserted`](ChangeKind::Inserted), [`Updated`](ChangeKind::Updated)
+/// or [`Removed`](ChangeKind::Removed) since the last [`reset()`](CdMap::reset()).
+/// Changes that undo each other, like an insert followed by a remove, cancel out.
+///
+/// Backed by a `HashMap` by default, or any other [`MapBackend`] such as a `BTreeMap`.
+/// Like `deref_mut()` on a [`Cd`](crate::Cd), handing out `&mut V` counts as an update
+/// even if the value ends up the same.
+///
+/// ```
+/// use changed::{CdMap, ChangeKind};
+/// use std::collections::BTreeMap;
+///
+/// let mut cd: CdMap<&str, i32, BTreeMap<_, _>> = CdMap::new(BTreeMap::new());
+/// cd.insert("a", 1);
+/// cd.insert("b", 2);
+/// cd.reset();
+///
+/// *cd.get_mut(&"a").unwrap() += 1;
+/// cd.remove(&"b");
+/// cd.insert("c", 3);
+///
+/// let changes: Vec<_> = cd.changes().collect();
+/// assert_eq!(
+///     changes,
+///     [(&"a", ChangeKind::Updated), (&"b", ChangeKind::Removed), (&"c", ChangeKind::Inserted)]
+/// );
+/// ```
+pub struct CdMap<K, V, M: MapBackend<K, V> = HashMap<K, V>> {
+    data: M,
+    changes: M::ChangeLog,
+}
+
+impl<K: Clone, V, M: MapBackend<K, V>> CdMap<K, V, M> {
+    /// Create a new CdMap with data.
+    /// It is initialized to false for change detection.
+    ///
+    /// ```
+    /// use changed::CdMap;
+    /// use std::collections::HashMap;
+    /// let cd: CdMap<i32, i32> = CdMap::new(HashMap::new());
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn new(data: M) -> CdMap<K, V, M> {
+        CdMap {
+            data,
+            changes: Default::default(),
+        }
+    }
+
+    /// Reset the change tracking to false.
+    /// ```
+    /// use changed::CdMap;
+    /// let mut cd: CdMap<i32, i32> = CdMap::default();
+    /// cd.insert(1, 1);
+    /// cd.reset();
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn reset(&mut self) {
+        self.changes = Default::default();
+    }
+
+    /// Take the data out of the CdMap.
+    /// Consumes self and returns data.
+    /// ```
+    /// use changed::CdMap;
+    /// let mut cd: CdMap<i32, i32> = CdMap::default();
+    /// cd.insert(1, 1);
+    /// assert_eq!(cd.take()[&1], 1);
+    /// ```
+    pub fn take(self) -> M {
+        self.data
+    }
+
+    /// Check if any key has been changed since the last call to reset (or created.)
+    /// ```
+    /// use changed::CdMap;
+    /// let mut cd: CdMap<i32, i32> = CdMap::default();
+    /// cd.insert(1, 1);
+    /// assert!(cd.changed());
+    /// ```
+    pub fn changed(&self) -> bool {
+        self.changes.iter().next().is_some()
+    }
+
+    /// Every key that changed since the last call to reset, and how.
+    /// ```
+    /// use changed::{CdMap, ChangeKind};
+    /// let mut cd: CdMap<i32, i32> = CdMap::default();
+    /// cd.insert(1, 1);
+    /// assert_eq!(cd.changes().collect::<Vec<_>>(), [(&1, ChangeKind::Inserted)]);
+    /// ```
+    pub fn changes(&self) -> impl Iterator<Item = (&K, ChangeKind)> {
+        self.changes.iter().map(|(key, kind)| (key, *kind))
+    }
+
+    /// How `key` changed since the last call to reset, if it did.
+    /// ```
+    /// use changed::{CdMap, ChangeKind};
+    /// let mut cd: CdMap<String, i32> = CdMap::default();
+    /// cd.insert("a".to_string(), 1);
+    /// cd.insert("a".to_string(), 2);
+    /// assert_eq!(cd.change("a"), Some(ChangeKind::Inserted));
+    /// assert_eq!(cd.change("b"), None);
+    /// ```
+    pub fn change<Q: ?Sized>(&self, key: &Q) -> Option<ChangeKind>
+    where
+        M::ChangeLog: MapLookup<Q, K, ChangeKind>,
+    {
+        MapLookup::get(&self.changes, key).copied()
+    }
+
+    /// Mutate the CdMap without tripping change detection.
+    ///
+    /// ```
+    /// use changed::CdMap;
+    /// let mut cd: CdMap<i32, i32> = CdMap::default();
+    /// cd.mutate_silently().insert(1, 1);
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn mutate_silently(&mut self) -> &mut M {
+        &mut self.data
+    }
+
+    /// Insert a value, recording the key as inserted, or updated if it was already there.
+    /// Returns the old value.
+    /// ```
+    /// use changed::{CdMap, ChangeKind};
+    /// let mut cd: CdMap<i32, i32> = CdMap::default();
+    /// cd.mutate_silently().insert(1, 1);
+    /// assert_eq!(cd.insert(1, 2), Some(1));
+    /// assert_eq!(cd.change(&1), Some(ChangeKind::Updated));
+    /// ```
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = self.data.insert(key.clone(), value);
+        let kind = match old {
+            Some(_) => ChangeKind::Updated,
+            None => ChangeKind::Inserted,
+        };
+        self.record(key, kind);
+        old
+    }
+
+    /// Mutably borrow the value of `key`, recording it as updated if it exists.
+    /// ```
+    /// use changed::{CdMap, ChangeKind};
+    /// let mut cd: CdMap<i32, i32> = CdMap::default();
+    /// cd.mutate_silently().insert(1, 1);
+    /// *cd.get_mut(&1).unwrap() += 1;
+    /// assert_eq!(cd.change(&1), Some(ChangeKind::Updated));
+    /// ```
+    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        M: MapLookup<Q, K, V>,
+    {
+        let (owned, _) = MapLookup::get_key_value(&self.data, key)?;
+        self.record(owned.clone(), ChangeKind::Updated);
+        MapLookup::get_mut(&mut self.data, key)
+    }
+
+    /// Remove `key`, recording it as removed if it was there. Returns the old value.
+    /// ```
+    /// use changed::CdMap;
+    /// let mut cd: CdMap<i32, i32> = CdMap::default();
+    /// cd.insert(1, 1);
+    /// assert_eq!(cd.remove(&1), Some(1));
+    /// // Inserted, then removed: no change.
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        M: MapLookup<Q, K, V>,
+    {
+        let (owned, old) = MapLookup::remove_entry(&mut self.data, key)?;
+        self.record(owned, ChangeKind::Removed);
+        Some(old)
+    }
+
+    /// Remove every key, recording each as removed.
+    /// ```
+    /// use changed::{CdMap, ChangeKind};
+    /// let mut cd: CdMap<i32, i32> = CdMap::default();
+    /// cd.mutate_silently().insert(1, 1);
+    /// cd.clear();
+    /// assert_eq!(cd.change(&1), Some(ChangeKind::Removed));
+    /// ```
+    pub fn clear(&mut self) {
+        let keys: Vec<K> = self.data.iter().map(|(key, _)| key.clone()).collect();
+        for key in keys {
+            self.record(key, ChangeKind::Removed);
+        }
+        self.data = M::default();
+    }
+
+    /// The entry for `key`, for in-place insertion or modification.
+    /// ```
+    /// use changed::{CdMap, ChangeKind};
+    /// let mut cd: CdMap<&str, i32> = CdMap::default();
+    /// *cd.entry("a").or_insert(0) += 1;
+    /// cd.entry("a").and_modify(|v| *v += 1).or_insert(0);
+    /// assert_eq!(cd[&"a"], 2);
+    /// assert_eq!(cd.change(&"a"), Some(ChangeKind::Inserted));
+    /// ```
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, M> {
+        Entry { map: self, key }
+    }
+
+    fn record(&mut self, key: K, kind: ChangeKind) {
+        let combined = match MapLookup::get(&self.changes, &key) {
+            Some(old) => old.then(kind),
+            None => Some(kind),
+        };
+        match combined {
+            Some(kind) => {
+                self.changes.insert(key, kind);
+            }
+            None => {
+                MapLookup::remove_entry(&mut self.changes, &key);
+            }
+        }
+    }
+}
+
+/// A key of a [`CdMap`], from [`entry()`](CdMap::entry()).
+pub struct Entry<'a, K, V, M: MapBackend<K, V>> {
+    map: &'a mut CdMap<K, V, M>,
+    key: K,
+}
+
+impl<'a, K: Clone, V, M: MapBackend<K, V>> Entry<'a, K, V, M> {
+    /// The key of this entry.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Modify the value if the key is in the map, recording it as updated.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        if let Some(value) = self.map.get_mut(&self.key) {
+            f(value);
+        }
+        self
+    }
+
+    /// Mutably borrow the value, inserting `default` first if the key is not in the map.
+    /// Records the key as inserted or updated.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    /// Mutably borrow the value, inserting the result of `default` first if the key is not in the map.
+    /// Records the key as inserted or updated.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        if MapLookup::get(&self.map.data, &self.key).is_none() {
+            self.map.insert(self.key.clone(), default());
+        } else {
+            self.map.record(self.key.clone(), ChangeKind::Updated);
+        }
+        MapLookup::get_mut(&mut self.map.data, &self.key).expect("key was just inserted")
+    }
+
+    /// Mutably borrow the value, inserting `V::default()` first if the key is not in the map.
+    /// Records the key as inserted or updated.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+}
+
+/// deref does not trip change detection.
+/// ```
+/// use changed::CdMap;
+/// let mut cd: CdMap<i32, i32> = CdMap::default();
+/// cd.insert(1, 1);
+/// cd.reset();
+/// assert_eq!(cd.get(&1), Some(&1));
+/// assert!(!cd.changed());
+/// ```
+impl<K, V, M: MapBackend<K, V>> Deref for CdMap<K, V, M> {
+    type Target = M;
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+/// Impl default. Change detection is initialized to false.
+/// ```
+/// use changed::CdMap;
+/// let empty: CdMap<i32, i32> = CdMap::default();
+/// assert!(!empty.changed());
+/// ```
+impl<K: Clone, V, M: MapBackend<K, V>> Default for CdMap<K, V, M> {
+    fn default() -> Self {
+        CdMap::new(M::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CdMap, ChangeKind};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn changes_combine() {
+        let mut cd: CdMap<i32, i32, BTreeMap<_, _>> = CdMap::default();
+        cd.mutate_silently().insert(1, 1);
+        cd.mutate_silently().insert(2, 2);
+
+        cd.remove(&1);
+        cd.insert(1, 10);
+        cd.get_mut(&2);
+        cd.remove(&2);
+        cd.insert(3, 3);
+        *cd.entry(3).or_default() += 1;
+        cd.get_mut(&4);
+        cd.remove(&4);
+
+        assert_eq!(
+            cd.changes().collect::<Vec<_>>(),
+            [
+                (&1, ChangeKind::Updated),
+                (&2, ChangeKind::Removed),
+                (&3, ChangeKind::Inserted)
+            ]
+        );
+        assert_eq!(cd.take().into_iter().collect::<Vec<_>>(), [(1, 10), (3, 4)]);
+    }
+}
@@ -0,0 +1,413 @@
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+use std::rc::{Rc, Weak};
+
+use crate::{Baseline, Cd, ChangeDetector, FlagDetector};
+
+type Callback<T, D> = Rc<RefCell<dyn FnMut(&Cd<T, D>)>>;
+
+/// CdObserved: Change Detection with callbacks
+///
+/// Closures registered with [`subscribe()`](CdObserved::subscribe()) run when a
+/// [`modify()`](CdObserved::modify()) guard is dropped, if the data counts as changed
+/// and the detector sees a change made through that guard. There is no `DerefMut`,
+/// since a plain `&mut T` cannot tell anyone when it is done.
+///
+/// ```
+/// use changed::CdObserved;
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// let mut cd = CdObserved::new(5);
+/// let seen = Rc::new(Cell::new(0));
+/// let sink = Rc::clone(&seen);
+/// let subscription = cd.subscribe(move |v| sink.set(*v));
+///
+/// *cd.modify() += 5;
+/// assert_eq!(seen.get(), 10);
+///
+/// // Dropping the subscription unsubscribes.
+/// drop(subscription);
+/// *cd.modify() += 5;
+/// assert_eq!(seen.get(), 10);
+/// ```
+pub struct CdObserved<T, D = FlagDetector> {
+    cd: Cd<T, D>,
+    observers: Rc<RefCell<Observers<T, D>>>,
+}
+
+struct Observers<T, D> {
+    next_id: u64,
+    callbacks: Vec<(u64, Callback<T, D>)>,
+}
+
+impl<T> CdObserved<T> {
+    /// Create a new CdObserved with data.
+    /// It is initialized to false for change detection.
+    ///
+    /// ```
+    /// use changed::CdObserved;
+    /// let cd = CdObserved::new(5);
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn new(data: T) -> CdObserved<T> {
+        CdObserved::from_cd(Cd::new(data))
+    }
+
+    /// Create a new CdObserved with data.
+    /// It is initialized to true for change detection.
+    /// ```
+    /// use changed::CdObserved;
+    /// let cd = CdObserved::new_true(5);
+    /// assert!(cd.changed());
+    /// ```
+    pub fn new_true(data: T) -> CdObserved<T> {
+        CdObserved::from_cd(Cd::new_true(data))
+    }
+}
+
+impl<T, D: ChangeDetector<T>> CdObserved<T, D> {
+    /// Create a new CdObserved with data, using detector for change detection.
+    /// It is initialized to false for change detection.
+    ///
+    /// ```
+    /// use changed::{CdObserved, EqDetector};
+    /// let cd = CdObserved::with_detector(5, EqDetector::new());
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn with_detector(data: T, detector: D) -> CdObserved<T, D> {
+        CdObserved::from_cd(Cd::with_detector(data, detector))
+    }
+
+    fn from_cd(cd: Cd<T, D>) -> CdObserved<T, D> {
+        CdObserved {
+            cd,
+            observers: Rc::new(RefCell::new(Observers {
+                next_id: 0,
+                callbacks: Vec::new(),
+            })),
+        }
+    }
+
+    /// Borrow the data mutably. Callbacks run when the guard is dropped.
+    ///
+    /// If the data had already changed, the guard checks a clone of the detector,
+    /// reset to the data as it was before the guard, so that writing the same value
+    /// again does not run the callbacks again.
+    /// ```
+    /// use changed::{CdObserved, EqDetector};
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
+    ///
+    /// let mut cd = CdObserved::with_detector(5, EqDetector::new());
+    /// let calls = Rc::new(Cell::new(0));
+    /// let counter = Rc::clone(&calls);
+    /// let _subscription = cd.subscribe(move |_| counter.set(counter.get() + 1));
+    ///
+    /// // Same value, so EqDetector does not count it as a change.
+    /// *cd.modify() = 5;
+    /// assert_eq!(calls.get(), 0);
+    /// *cd.modify() = 6;
+    /// *cd.modify() = 6;
+    /// assert_eq!(calls.get(), 1);
+    /// ```
+    pub fn modify(&mut self) -> ObserveGuard<'_, T, D>
+    where
+        D: Clone,
+    {
+        ObserveGuard {
+            was_changed: self.changed(),
+            observed: self,
+            touched: false,
+            probe: None,
+        }
+    }
+
+    /// Replace the data, then run callbacks if it counts as changed.
+    /// ```
+    /// use changed::CdObserved;
+    /// let mut cd = CdObserved::new(5);
+    /// cd.set(10);
+    /// assert_eq!(*cd, 10);
+    /// assert!(cd.changed());
+    /// ```
+    pub fn set(&mut self, data: T)
+    where
+        D: Clone,
+    {
+        *self.modify() = data;
+    }
+
+    /// Reset the change tracking to false. Does not run callbacks.
+    /// ```
+    /// use changed::CdObserved;
+    /// let mut cd = CdObserved::new_true(5);
+    /// cd.reset();
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn reset(&mut self) {
+        self.cd.reset();
+    }
+
+    /// Take the data out of the CdObserved.
+    /// Consumes self and returns data.
+    /// ```
+    /// use changed::CdObserved;
+    /// let cd = CdObserved::new(5);
+    /// assert_eq!(cd.take(), 5);
+    /// ```
+    pub fn take(self) -> T {
+        self.cd.take()
+    }
+
+    /// Check if the CdObserved has been changed since the last call to reset (or created.)
+    /// ```
+    /// use changed::CdObserved;
+    /// let mut cd = CdObserved::new(5);
+    /// *cd.modify() += 5;
+    /// assert!(cd.changed());
+    /// ```
+    pub fn changed(&self) -> bool {
+        self.cd.changed()
+    }
+
+    /// Mutate the CdObserved without tripping change detection or running callbacks.
+    ///
+    /// ```
+    /// use changed::CdObserved;
+    /// let mut cd = CdObserved::new(5);
+    /// *cd.mutate_silently() += 5;
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn mutate_silently(&mut self) -> &mut T {
+        self.cd.mutate_silently()
+    }
+
+    fn notify(&self) {
+        let callbacks: Vec<_> = self.observers.borrow().callbacks.clone();
+        for (id, callback) in callbacks {
+            // An earlier callback may have dropped this subscription.
+            let subscribed = self
+                .observers
+                .borrow()
+                .callbacks
+                .iter()
+                .any(|(i, _)| *i == id);
+            if subscribed {
+                (callback.borrow_mut())(&self.cd);
+            }
+        }
+    }
+}
+
+impl<T: 'static, D: ChangeDetector<T> + 'static> CdObserved<T, D> {
+    /// Run `callback` with the data whenever it changes.
+    /// The callback is unsubscribed when the returned [`Subscription`] is dropped.
+    pub fn subscribe<F: FnMut(&T) + 'static>(&mut self, mut callback: F) -> Subscription {
+        self.add(Rc::new(RefCell::new(move |cd: &Cd<T, D>| callback(cd))))
+    }
+
+    /// Run `callback` with the data and its [`baseline()`](Cd::baseline()) whenever it changes.
+    /// The callback is unsubscribed when the returned [`Subscription`] is dropped.
+    ///
+    /// ```
+    /// use changed::{CdObserved, EqDetector};
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
+    ///
+    /// let mut cd = CdObserved::with_detector(5, EqDetector::new());
+    /// let delta = Rc::new(Cell::new(0));
+    /// let sink = Rc::clone(&delta);
+    /// let _subscription = cd.subscribe_with_previous(move |now, before| sink.set(now - before));
+    ///
+    /// *cd.modify() += 3;
+    /// assert_eq!(delta.get(), 3);
+    /// ```
+    pub fn subscribe_with_previous<F: FnMut(&T, &T) + 'static>(
+        &mut self,
+        mut callback: F,
+    ) -> Subscription
+    where
+        D: Baseline<T>,
+    {
+        self.add(Rc::new(RefCell::new(move |cd: &Cd<T, D>| {
+            callback(cd, cd.baseline())
+        })))
+    }
+
+    fn add(&mut self, callback: Callback<T, D>) -> Subscription {
+        let mut observers = self.observers.borrow_mut();
+        let id = observers.next_id;
+        observers.next_id += 1;
+        observers.callbacks.push((id, callback));
+        let list: Rc<dyn Unsubscribe> = self.observers.clone();
+        Subscription {
+            list: Rc::downgrade(&list),
+            id,
+        }
+    }
+}
+
+/// deref does not trip change detection.
+/// ```
+/// use changed::CdObserved;
+/// let cd = CdObserved::new(5);
+/// assert_eq!(*cd, 5);
+/// assert!(!cd.changed());
+/// ```
+impl<T, D> Deref for CdObserved<T, D> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.cd
+    }
+}
+
+/// Impl default where the data and detector impl default. Change detection is initialized to false.
+/// ```
+/// use changed::CdObserved;
+/// let zero: CdObserved<i32> = CdObserved::default();
+/// assert!(!zero.changed());
+/// ```
+impl<T: Default, D: ChangeDetector<T> + Default> Default for CdObserved<T, D> {
+    fn default() -> Self {
+        CdObserved::from_cd(Cd::default())
+    }
+}
+
+/// Mutable access to the data of a [`CdObserved`], from [`modify()`](CdObserved::modify()).
+///
+/// `deref_mut()` trips change detection, and dropping the guard runs the callbacks.
+pub struct ObserveGuard<'a, T, D: ChangeDetector<T>> {
+    observed: &'a mut CdObserved<T, D>,
+    was_changed: bool,
+    touched: bool,
+    /// Only sees the changes made through this guard, if the data had already changed.
+    probe: Option<D>,
+}
+
+impl<T, D: ChangeDetector<T>> Deref for ObserveGuard<'_, T, D> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.observed.cd
+    }
+}
+
+impl<T, D: ChangeDetector<T> + Clone> DerefMut for ObserveGuard<'_, T, D> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        let cd = &mut self.observed.cd;
+        if !self.touched && self.was_changed {
+            let mut probe = cd.detector.clone();
+            probe.on_reset(&cd.data);
+            self.probe = Some(probe);
+        }
+        self.touched = true;
+        if let Some(probe) = &mut self.probe {
+            probe.on_mutate(&cd.data);
+        }
+        cd
+    }
+}
+
+impl<T, D: ChangeDetector<T>> Drop for ObserveGuard<'_, T, D> {
+    fn drop(&mut self) {
+        if !self.touched || !self.observed.changed() {
+            return;
+        }
+        let changed_here = match &self.probe {
+            Some(probe) => probe.is_changed(&self.observed.cd.data),
+            None => true,
+        };
+        if changed_here {
+            self.observed.notify();
+        }
+    }
+}
+
+trait Unsubscribe {
+    fn unsubscribe(&self, id: u64);
+}
+
+impl<T, D> Unsubscribe for RefCell<Observers<T, D>> {
+    fn unsubscribe(&self, id: u64) {
+        self.borrow_mut().callbacks.retain(|(i, _)| *i != id);
+    }
+}
+
+/// Keeps a callback subscribed to a [`CdObserved`]. Unsubscribes when dropped.
+pub struct Subscription {
+    list: Weak<dyn Unsubscribe>,
+    id: u64,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(list) = self.list.upgrade() {
+            list.unsubscribe(self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CdObserved, EqDetector};
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    #[test]
+    fn reads_through_the_guard_do_not_notify() {
+        let mut cd = CdObserved::new(5);
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+        let _subscription = cd.subscribe(move |_| counter.set(counter.get() + 1));
+        assert_eq!(*cd.modify(), 5);
+        assert_eq!(calls.get(), 0);
+        assert!(!cd.changed());
+    }
+
+    #[test]
+    fn callbacks_can_drop_other_subscriptions() {
+        let mut cd = CdObserved::new(0);
+        let calls = Rc::new(Cell::new(0));
+        let victim = Rc::new(RefCell::new(None));
+
+        let dropper = Rc::clone(&victim);
+        let _first = cd.subscribe(move |_| drop(dropper.borrow_mut().take()));
+        let counter = Rc::clone(&calls);
+        *victim.borrow_mut() = Some(cd.subscribe(move |_| counter.set(counter.get() + 1)));
+
+        cd.set(1);
+        assert_eq!(calls.get(), 0);
+    }
+
+    #[test]
+    fn subscriptions_outlive_the_value() {
+        let mut cd = CdObserved::new(0);
+        let subscription = cd.subscribe(|_| {});
+        drop(cd);
+        drop(subscription);
+    }
+
+    #[test]
+    fn callbacks_run_once_per_change() {
+        let mut eq = CdObserved::with_detector(5, EqDetector::new());
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+        let _subscription = eq.subscribe(move |_| counter.set(counter.get() + 1));
+        for _ in 0..3 {
+            eq.set(6);
+        }
+        assert_eq!(calls.get(), 1);
+        eq.set(7);
+        assert_eq!(calls.get(), 2);
+
+        // Every write counts for a flag.
+        let mut flag = CdObserved::new(5);
+        let counter = Rc::clone(&calls);
+        let _subscription = flag.subscribe(move |_| counter.set(counter.get() + 1));
+        flag.set(5);
+        flag.set(5);
+        assert_eq!(calls.get(), 4);
+    }
+}
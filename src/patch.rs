@@ -0,0 +1,502 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Baseline, Cd, ChangeDetector};
+
+/// One operation of an [RFC 6902](https://www.rfc-editor.org/rfc/rfc6902) JSON Patch.
+///
+/// Serializes to the standard form, such as `{"op":"add","path":"/a/0","value":1}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+    /// Add `value` at `path`, inserting into arrays.
+    Add {
+        /// A JSON pointer.
+        path: String,
+        /// The value to add.
+        value: Value,
+    },
+    /// Remove the value at `path`.
+    Remove {
+        /// A JSON pointer.
+        path: String,
+    },
+    /// Replace the value at `path`.
+    Replace {
+        /// A JSON pointer.
+        path: String,
+        /// The new value.
+        value: Value,
+    },
+    /// Remove the value at `from` and add it at `path`.
+    Move {
+        /// A JSON pointer to the value to move.
+        from: String,
+        /// A JSON pointer to where it goes.
+        path: String,
+    },
+    /// Add a copy of the value at `from` at `path`.
+    Copy {
+        /// A JSON pointer to the value to copy.
+        from: String,
+        /// A JSON pointer to where the copy goes.
+        path: String,
+    },
+    /// Fail the patch unless the value at `path` is `value`.
+    Test {
+        /// A JSON pointer.
+        path: String,
+        /// The expected value.
+        value: Value,
+    },
+}
+
+/// Why a JSON Patch could not be made or applied.
+#[derive(Debug)]
+pub enum PatchError {
+    /// The data could not be converted to or from JSON.
+    Json(serde_json::Error),
+    /// A path does not point to somewhere the operation can act on.
+    InvalidPath(String),
+    /// A `test` operation failed.
+    TestFailed(String),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Json(err) => write!(f, "JSON conversion failed: {}", err),
+            PatchError::InvalidPath(path) => write!(f, "invalid path {:?}", path),
+            PatchError::TestFailed(path) => write!(f, "test failed at {:?}", path),
+        }
+    }
+}
+
+impl Error for PatchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PatchError::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for PatchError {
+    fn from(err: serde_json::Error) -> Self {
+        PatchError::Json(err)
+    }
+}
+
+impl<T: Serialize, D: Baseline<T>> Cd<T, D> {
+    /// A JSON Patch that turns the [`baseline()`](Cd::baseline()) into the current data.
+    ///
+    /// Elements that moved within an array become `move` operations rather than
+    /// a remove and an add.
+    ///
+    /// ```
+    /// use changed::{Cd, EqDetector, PatchOp};
+    /// use serde_json::json;
+    ///
+    /// let mut cd = Cd::with_detector(json!({"todo": ["a", "b", "c"]}), EqDetector::new());
+    /// cd["todo"] = json!(["c", "a", "b"]);
+    /// assert_eq!(
+    ///     cd.json_patch().unwrap(),
+    ///     [PatchOp::Move { from: "/todo/2".into(), path: "/todo/0".into() }]
+    /// );
+    /// ```
+    pub fn json_patch(&self) -> Result<Vec<PatchOp>, PatchError> {
+        let before = serde_json::to_value(self.baseline())?;
+        let after = serde_json::to_value(&**self)?;
+        let mut patch = Vec::new();
+        diff(&mut String::new(), &before, &after, &mut patch);
+        Ok(patch)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned, D: ChangeDetector<T>> Cd<T, D> {
+    /// Apply a JSON Patch to the data. Trips change detection like `deref_mut()`.
+    ///
+    /// The data is only written if every operation succeeds and the result
+    /// converts back into `T`. Otherwise it is left as it was.
+    ///
+    /// ```
+    /// use changed::{Cd, PatchOp};
+    /// use serde_json::json;
+    ///
+    /// let mut cd = Cd::new(vec![1, 2]);
+    /// let patch: Vec<PatchOp> = serde_json::from_value(json!([
+    ///     {"op": "add", "path": "/-", "value": 3},
+    ///     {"op": "replace", "path": "/0", "value": 0},
+    /// ]))
+    /// .unwrap();
+    /// cd.apply_json_patch(&patch).unwrap();
+    /// assert_eq!(*cd, [0, 2, 3]);
+    /// assert!(cd.changed());
+    ///
+    /// let bad = [PatchOp::Remove { path: "/9".into() }];
+    /// assert!(cd.apply_json_patch(&bad).is_err());
+    /// assert_eq!(*cd, [0, 2, 3]);
+    /// ```
+    pub fn apply_json_patch(&mut self, patch: &[PatchOp]) -> Result<(), PatchError> {
+        let mut value = serde_json::to_value(&**self)?;
+        apply(&mut value, patch)?;
+        **self = serde_json::from_value(value)?;
+        Ok(())
+    }
+}
+
+/// Append the operations that turn `before` into `after`, both found at `path`.
+fn diff(path: &mut String, before: &Value, after: &Value, patch: &mut Vec<PatchOp>) {
+    if before == after {
+        return;
+    }
+    match (before, after) {
+        (Value::Object(before), Value::Object(after)) => {
+            for (key, old) in before {
+                with_token(path, key, |path| match after.get(key) {
+                    Some(new) => diff(path, old, new, patch),
+                    None => patch.push(PatchOp::Remove { path: path.clone() }),
+                });
+            }
+            for (key, new) in after {
+                if !before.contains_key(key) {
+                    with_token(path, key, |path| {
+                        patch.push(PatchOp::Add {
+                            path: path.clone(),
+                            value: new.clone(),
+                        })
+                    });
+                }
+            }
+        }
+        (Value::Array(before), Value::Array(after)) => diff_array(path, before, after, patch),
+        _ => patch.push(PatchOp::Replace {
+            path: path.clone(),
+            value: after.clone(),
+        }),
+    }
+}
+
+/// Greedily make `before` match `after` one index at a time, keeping a working copy in
+/// step with the operations emitted so far so every index is valid when it is applied.
+fn diff_array(path: &mut String, before: &[Value], after: &[Value], patch: &mut Vec<PatchOp>) {
+    let mut current = before.to_vec();
+    for (i, new) in after.iter().enumerate() {
+        if current.get(i) == Some(new) {
+            continue;
+        }
+        let index = i.to_string();
+        // An element from further along that belongs here.
+        if let Some(j) = (i + 1..current.len()).find(|&j| current[j] == *new) {
+            let from = with_token(path, &j.to_string(), |from| from.clone());
+            with_token(path, &index, |path| {
+                patch.push(PatchOp::Move {
+                    from,
+                    path: path.clone(),
+                })
+            });
+            let moved = current.remove(j);
+            current.insert(i, moved);
+            continue;
+        }
+        // Edit the element in place, unless it is still needed further along.
+        let reusable = match current.get(i) {
+            Some(old) => !after[i + 1..].contains(old),
+            None => false,
+        };
+        with_token(path, &index, |path| {
+            if reusable {
+                diff(path, &current[i], new, patch);
+                current[i] = new.clone();
+            } else {
+                patch.push(PatchOp::Add {
+                    path: path.clone(),
+                    value: new.clone(),
+                });
+                current.insert(i, new.clone());
+            }
+        });
+    }
+    for i in (after.len()..current.len()).rev() {
+        with_token(path, &i.to_string(), |path| {
+            patch.push(PatchOp::Remove { path: path.clone() })
+        });
+    }
+}
+
+/// Run `f` with `token` appended to the JSON pointer `path`.
+fn with_token<R>(path: &mut String, token: &str, f: impl FnOnce(&mut String) -> R) -> R {
+    let len = path.len();
+    path.push('/');
+    for c in token.chars() {
+        match c {
+            '~' => path.push_str("~0"),
+            '/' => path.push_str("~1"),
+            c => path.push(c),
+        }
+    }
+    let out = f(path);
+    path.truncate(len);
+    out
+}
+
+fn apply(doc: &mut Value, patch: &[PatchOp]) -> Result<(), PatchError> {
+    for op in patch {
+        match op {
+            PatchOp::Add { path, value } => add(doc, path, value.clone())?,
+            PatchOp::Remove { path } => {
+                remove(doc, path)?;
+            }
+            PatchOp::Replace { path, value } => *get_mut(doc, path)? = value.clone(),
+            PatchOp::Move { from, path } => {
+                if path.starts_with(from.as_str()) && path[from.len()..].starts_with('/') {
+                    return Err(PatchError::InvalidPath(path.clone()));
+                }
+                let value = remove(doc, from)?;
+                add(doc, path, value)?;
+            }
+            PatchOp::Copy { from, path } => {
+                let value = get_mut(doc, from)?.clone();
+                add(doc, path, value)?;
+            }
+            PatchOp::Test { path, value } => {
+                if get_mut(doc, path)? != value {
+                    return Err(PatchError::TestFailed(path.clone()));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Split a JSON pointer into its parent and its unescaped last token.
+fn split(path: &str) -> Result<(&str, String), PatchError> {
+    match path.rfind('/') {
+        Some(i) => Ok((&path[..i], unescape(&path[i + 1..]))),
+        None => Err(PatchError::InvalidPath(path.to_string())),
+    }
+}
+
+fn unescape(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+/// Parse an array index, which may not have leading zeros.
+fn index(token: &str, path: &str) -> Result<usize, PatchError> {
+    let valid = !token.is_empty()
+        && token.bytes().all(|b| b.is_ascii_digit())
+        && (token == "0" || !token.starts_with('0'));
+    match token.parse() {
+        Ok(i) if valid => Ok(i),
+        _ => Err(PatchError::InvalidPath(path.to_string())),
+    }
+}
+
+fn get_mut<'a>(doc: &'a mut Value, path: &str) -> Result<&'a mut Value, PatchError> {
+    if path.is_empty() {
+        return Ok(doc);
+    }
+    if !path.starts_with('/') {
+        return Err(PatchError::InvalidPath(path.to_string()));
+    }
+    let mut value = doc;
+    for token in path[1..].split('/') {
+        let token = unescape(token);
+        value = match value {
+            Value::Object(map) => map.get_mut(&token),
+            Value::Array(array) => array.get_mut(index(&token, path)?),
+            _ => None,
+        }
+        .ok_or_else(|| PatchError::InvalidPath(path.to_string()))?;
+    }
+    Ok(value)
+}
+
+fn add(doc: &mut Value, path: &str, value: Value) -> Result<(), PatchError> {
+    if path.is_empty() {
+        *doc = value;
+        return Ok(());
+    }
+    let (parent, token) = split(path)?;
+    match get_mut(doc, parent)? {
+        Value::Object(map) => {
+            map.insert(token, value);
+        }
+        Value::Array(array) if token == "-" => array.push(value),
+        Value::Array(array) => {
+            let i = index(&token, path)?;
+            if i > array.len() {
+                return Err(PatchError::InvalidPath(path.to_string()));
+            }
+            array.insert(i, value);
+        }
+        _ => return Err(PatchError::InvalidPath(path.to_string())),
+    }
+    Ok(())
+}
+
+fn remove(doc: &mut Value, path: &str) -> Result<Value, PatchError> {
+    let (parent, token) = split(path)?;
+    let removed = match get_mut(doc, parent)? {
+        Value::Object(map) => map.remove(&token),
+        Value::Array(array) => {
+            let i = index(&token, path)?;
+            if i < array.len() {
+                Some(array.remove(i))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+    removed.ok_or_else(|| PatchError::InvalidPath(path.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, diff, PatchOp};
+    use crate::{Cd, EqDetector};
+    use serde_json::{json, Map, Value};
+
+    /// xorshift64, so the tests are reproducible without pulling in a rand crate.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    fn key(rng: &mut Rng) -> String {
+        // Few keys, so objects share some, and some need escaping.
+        ["a", "b", "c/d", "e~f", ""][rng.below(5)].to_string()
+    }
+
+    fn value(rng: &mut Rng, depth: u32) -> Value {
+        let kinds = if depth == 0 { 3 } else { 5 };
+        match rng.below(kinds) {
+            0 => Value::Null,
+            1 => json!(rng.below(4)),
+            2 => json!(["x", "y"][rng.below(2)]),
+            3 => (0..rng.below(5)).map(|_| value(rng, depth - 1)).collect(),
+            _ => {
+                let mut map = Map::new();
+                for _ in 0..rng.below(4) {
+                    map.insert(key(rng), value(rng, depth - 1));
+                }
+                Value::Object(map)
+            }
+        }
+    }
+
+    /// A variation of `v`: some elements changed, shuffled, dropped or added.
+    fn mutate(rng: &mut Rng, v: &Value, depth: u32) -> Value {
+        match v {
+            Value::Array(items) => {
+                let mut items: Vec<Value> = items
+                    .iter()
+                    .filter_map(|item| {
+                        let keep = rng.below(5) != 0;
+                        keep.then(|| mutate(rng, item, depth.saturating_sub(1)))
+                    })
+                    .collect();
+                for _ in 0..rng.below(3) {
+                    if !items.is_empty() {
+                        let (i, j) = (rng.below(items.len()), rng.below(items.len()));
+                        items.swap(i, j);
+                    }
+                }
+                for _ in 0..rng.below(3) {
+                    let i = rng.below(items.len() + 1);
+                    items.insert(i, value(rng, depth));
+                }
+                Value::Array(items)
+            }
+            Value::Object(map) => {
+                let mut map: Map<String, Value> = map
+                    .iter()
+                    .filter_map(|(k, v)| {
+                        let keep = rng.below(5) != 0;
+                        keep.then(|| (k.clone(), mutate(rng, v, depth.saturating_sub(1))))
+                    })
+                    .collect();
+                if rng.below(2) == 0 {
+                    map.insert(key(rng), value(rng, depth));
+                }
+                Value::Object(map)
+            }
+            _ if rng.below(3) == 0 => value(rng, depth),
+            _ => v.clone(),
+        }
+    }
+
+    #[test]
+    fn patches_round_trip() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..2000 {
+            let before = value(&mut rng, 3);
+            let after = mutate(&mut rng, &before, 3);
+
+            let mut patch = Vec::new();
+            diff(&mut String::new(), &before, &after, &mut patch);
+            assert_eq!(patch.is_empty(), before == after);
+
+            let json = serde_json::to_string(&patch).unwrap();
+            let patch: Vec<PatchOp> = serde_json::from_str(&json).unwrap();
+            let mut patched = before.clone();
+            apply(&mut patched, &patch).unwrap();
+            assert_eq!(patched, after, "patch: {}", json);
+        }
+    }
+
+    #[test]
+    fn patches_apply_to_a_receiving_cd() {
+        let mut sender = Cd::with_detector(json!({"tags": [1, 2, 3, 4]}), EqDetector::new());
+        let mut receiver = Cd::new(sender.clone());
+        sender["tags"] = json!([4, 1, 2, 3, 5]);
+        let patch = sender.json_patch().unwrap();
+        assert_eq!(patch.len(), 2);
+
+        receiver.apply_json_patch(&patch).unwrap();
+        assert_eq!(*receiver, *sender);
+        assert!(receiver.changed());
+    }
+
+    #[test]
+    fn standard_operations() {
+        let mut doc = json!({"a/b": {"c~d": [1, 2]}, "e": 1});
+        let patch: Vec<PatchOp> = serde_json::from_value(json!([
+            {"op": "test", "path": "/a~1b/c~0d/1", "value": 2},
+            {"op": "copy", "from": "/a~1b/c~0d", "path": "/f"},
+            {"op": "move", "from": "/e", "path": "/f/0"},
+            {"op": "remove", "path": "/a~1b"},
+            {"op": "add", "path": "/f/-", "value": 3},
+        ]))
+        .unwrap();
+        apply(&mut doc, &patch).unwrap();
+        assert_eq!(doc, json!({"f": [1, 1, 2, 3]}));
+
+        let failing = [
+            json!({"op": "test", "path": "/f/0", "value": 2}),
+            json!({"op": "remove", "path": "/f/01"}),
+            json!({"op": "add", "path": "/f/5", "value": 0}),
+            json!({"op": "move", "from": "/f", "path": "/f/0"}),
+            json!({"op": "replace", "path": "f", "value": 0}),
+        ];
+        for op in failing {
+            let op: PatchOp = serde_json::from_value(op).unwrap();
+            assert!(apply(&mut doc, &[op]).is_err());
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{Baseline, Cd, ChangeDetector};
+
+/// CdPrevious: Change Detection that remembers the previous value
+///
+/// The first `deref_mut()` after a [`reset()`](Cd::reset()) clones the data before
+/// handing it out, so [`previous()`](Cd::previous()) is the value as of the last reset.
+/// Reads never clone. Like a plain [`Cd`], any `deref_mut()` counts as a change.
+///
+/// ```
+/// use changed::{CdPrevious, NewCd};
+/// let mut position = CdPrevious::new(1.0);
+/// *position += 0.5;
+/// *position += 0.5;
+/// assert_eq!(*position.previous(), 1.0);
+/// assert_eq!(position.delta(), 1.0);
+///
+/// position.reset();
+/// assert_eq!(*position.previous(), 2.0);
+/// ```
+pub type CdPrevious<T> = Cd<T, PreviousDetector<T>>;
+
+/// Clones the data on the first `deref_mut()` after a reset.
+#[derive(Debug, Clone)]
+pub struct PreviousDetector<T> {
+    previous: Option<T>,
+    forced: bool,
+}
+
+impl<T> PreviousDetector<T> {
+    /// Create a PreviousDetector.
+    pub fn new() -> PreviousDetector<T> {
+        PreviousDetector {
+            previous: None,
+            forced: false,
+        }
+    }
+}
+
+impl<T> Default for PreviousDetector<T> {
+    fn default() -> Self {
+        PreviousDetector::new()
+    }
+}
+
+impl<T: Clone> ChangeDetector<T> for PreviousDetector<T> {
+    fn on_mutate(&mut self, data: &T) {
+        if self.previous.is_none() {
+            self.previous = Some(data.clone());
+        }
+    }
+
+    fn is_changed(&self, _data: &T) -> bool {
+        self.forced || self.previous.is_some()
+    }
+
+    fn on_reset(&mut self, _data: &T) {
+        self.previous = None;
+        self.forced = false;
+    }
+
+    fn mark_changed(&mut self) {
+        self.forced = true;
+    }
+}
+
+impl<T: Clone> Baseline<T> for PreviousDetector<T> {
+    fn baseline<'a>(&'a self, data: &'a T) -> &'a T {
+        self.previous.as_ref().unwrap_or(data)
+    }
+}
+
+impl<T: Clone> Cd<T, PreviousDetector<T>> {
+    /// The data as of the last reset (or creation.)
+    /// Same as [`baseline()`](Cd::baseline()).
+    /// ```
+    /// use changed::{Cd, PreviousDetector};
+    /// let mut cd = Cd::with_detector(5, PreviousDetector::new());
+    /// assert_eq!(*cd.previous(), 5);
+    /// *cd = 10;
+    /// assert_eq!(*cd.previous(), 5);
+    /// ```
+    pub fn previous(&self) -> &T {
+        self.baseline()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cd, PreviousDetector};
+    use std::cell::Cell;
+
+    thread_local! {
+        static CLONES: Cell<usize> = const { Cell::new(0) };
+    }
+
+    struct Counted(i32);
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            CLONES.with(|c| c.set(c.get() + 1));
+            Counted(self.0)
+        }
+    }
+
+    #[test]
+    fn clones_once_per_reset() {
+        let mut cd = Cd::with_detector(Counted(1), PreviousDetector::new());
+        assert_eq!(cd.0 + cd.previous().0, 2);
+        assert_eq!(CLONES.with(Cell::get), 0);
+
+        cd.0 += 1;
+        cd.0 += 1;
+        assert_eq!(CLONES.with(Cell::get), 1);
+        assert_eq!(cd.previous().0, 1);
+
+        cd.reset();
+        assert_eq!(cd.replace(Counted(10)).0, 3);
+        assert_eq!(CLONES.with(Cell::get), 2);
+        assert_eq!(cd.previous().0, 3);
+    }
+}
@@ -0,0 +1,458 @@
+//! An incremental query [`Database`]: derived values that are only recomputed when
+//! the inputs they read have changed.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::{Cd, GenerationDetector, Tick};
+
+/// Holds inputs and memoized queries.
+///
+/// Inputs are [`Cd`]s with a [`GenerationDetector`]. A query records every input it reads
+/// along with its tick, and every other query it calls. Its result is reused until one of
+/// those changes. If a recomputed result equals the previous one, queries that depend
+/// on it are not recomputed either (early cutoff).
+///
+/// ```
+/// use changed::query::Database;
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// let mut db = Database::new();
+/// let source = db.input(String::from("fn main() {}"));
+///
+/// let runs = Rc::new(Cell::new(0));
+/// let counter = Rc::clone(&runs);
+/// let lines = db.query(move |db, _: &()| {
+///     counter.set(counter.get() + 1);
+///     Ok(db.read(source).lines().count())
+/// });
+///
+/// assert_eq!(db.get(lines, &()), Ok(1));
+/// assert_eq!(db.get(lines, &()), Ok(1));
+/// assert_eq!(runs.get(), 1);
+///
+/// db.update(source, |s| s.push_str("\nfn other() {}"));
+/// assert_eq!(db.get(lines, &()), Ok(2));
+/// assert_eq!(runs.get(), 2);
+/// ```
+pub struct Database {
+    revision: u64,
+    inputs: Vec<Cd<Box<dyn Any>, GenerationDetector>>,
+    queries: Vec<Rc<dyn Storage>>,
+    /// The dependencies of each running query, innermost last.
+    stack: RefCell<Vec<Vec<Dep>>>,
+}
+
+/// A handle to an input of a [`Database`].
+pub struct Input<T> {
+    id: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// A handle to a query of a [`Database`], computing a `V` for each key `K`.
+pub struct Query<K, V> {
+    id: usize,
+    _marker: PhantomData<fn(&K) -> V>,
+}
+
+/// A query depends on itself, directly or through other queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle;
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("query depends on itself")
+    }
+}
+
+impl Error for Cycle {}
+
+#[derive(Clone)]
+enum Dep {
+    Input { id: usize, tick: Tick },
+    Query { id: usize, key: Rc<dyn Any> },
+}
+
+impl Database {
+    /// Create an empty Database.
+    pub fn new() -> Database {
+        Database {
+            revision: 0,
+            inputs: Vec::new(),
+            queries: Vec::new(),
+            stack: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Add an input.
+    pub fn input<T: 'static>(&mut self, value: T) -> Input<T> {
+        self.inputs.push(Cd::with_detector(
+            Box::new(value),
+            GenerationDetector::default(),
+        ));
+        Input {
+            id: self.inputs.len() - 1,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Read an input. Inside a query, the query now depends on it.
+    pub fn read<T: 'static>(&self, input: Input<T>) -> &T {
+        let cd = &self.inputs[input.id];
+        self.record(Dep::Input {
+            id: input.id,
+            tick: cd.tick(),
+        });
+        cd.downcast_ref().expect("input belongs to this database")
+    }
+
+    /// Replace an input. Queries that read it are recomputed when next asked for.
+    pub fn set<T: 'static>(&mut self, input: Input<T>, value: T) {
+        self.update(input, |v| *v = value);
+    }
+
+    /// Mutate an input. Queries that read it are recomputed when next asked for.
+    pub fn update<T: 'static>(&mut self, input: Input<T>, f: impl FnOnce(&mut T)) {
+        self.revision += 1;
+        let cd = &mut self.inputs[input.id];
+        f(cd.downcast_mut().expect("input belongs to this database"));
+    }
+
+    /// Add a query. `f` computes the value for a key, reading inputs with
+    /// [`read()`](Database::read()) and other queries with [`get()`](Database::get()).
+    pub fn query<K, V, F>(&mut self, f: F) -> Query<K, V>
+    where
+        K: Clone + Eq + Hash + 'static,
+        V: Clone + PartialEq + 'static,
+        F: Fn(&Database, &K) -> Result<V, Cycle> + 'static,
+    {
+        self.queries.push(Rc::new(QueryStorage {
+            f: Box::new(f),
+            slots: RefCell::new(HashMap::new()),
+        }));
+        Query {
+            id: self.queries.len() - 1,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The value of a query for `key`, reused if nothing it read has changed.
+    /// Inside a query, the query now depends on it.
+    ///
+    /// Returns [`Cycle`] if computing it needs its own value.
+    ///
+    /// ```
+    /// use changed::query::Database;
+    /// let mut db = Database::new();
+    /// let width = db.input(3);
+    /// let area = db.query(move |db, &height: &i32| Ok(db.read(width) * height));
+    /// let total = db.query(move |db, heights: &Vec<i32>| {
+    ///     heights.iter().map(|h| db.get(area, h)).sum()
+    /// });
+    /// assert_eq!(db.get(total, &vec![1, 2]), Ok(9));
+    /// ```
+    pub fn get<K, V>(&self, query: Query<K, V>, key: &K) -> Result<V, Cycle>
+    where
+        K: Clone + Eq + Hash + 'static,
+        V: Clone + PartialEq + 'static,
+    {
+        let storage = Rc::clone(&self.queries[query.id]);
+        let storage: &QueryStorage<K, V> = storage
+            .as_any()
+            .downcast_ref()
+            .expect("query belongs to this database");
+        storage.refresh(self, key)?;
+        self.record(Dep::Query {
+            id: query.id,
+            key: Rc::new(key.clone()),
+        });
+        let slots = storage.slots.borrow();
+        let memo = slots[key].memo.as_ref().expect("refreshed");
+        Ok(memo.value.clone())
+    }
+
+    fn record(&self, dep: Dep) {
+        if let Some(deps) = self.stack.borrow_mut().last_mut() {
+            deps.push(dep);
+        }
+    }
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Database::new()
+    }
+}
+
+impl<T> Clone for Input<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Input<T> {}
+
+impl<K, V> Clone for Query<K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V> Copy for Query<K, V> {}
+
+/// A query with its keys type-erased, so dependencies on it can be checked.
+trait Storage {
+    /// Bring the value for `key` up to date, returning the revision it last changed in.
+    fn verify(&self, db: &Database, key: &dyn Any) -> Result<u64, Cycle>;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+type QueryFn<K, V> = Box<dyn Fn(&Database, &K) -> Result<V, Cycle>>;
+
+struct QueryStorage<K, V> {
+    f: QueryFn<K, V>,
+    slots: RefCell<HashMap<K, Slot<V>>>,
+}
+
+struct Slot<V> {
+    memo: Option<Memo<V>>,
+    /// Set while the value is being verified or computed, to catch cycles.
+    active: bool,
+}
+
+struct Memo<V> {
+    value: V,
+    deps: Rc<[Dep]>,
+    verified_at: u64,
+    changed_at: u64,
+}
+
+impl<K, V> QueryStorage<K, V>
+where
+    K: Clone + Eq + Hash + 'static,
+    V: Clone + PartialEq + 'static,
+{
+    fn refresh(&self, db: &Database, key: &K) -> Result<u64, Cycle> {
+        let previous = {
+            let mut slots = self.slots.borrow_mut();
+            let slot = slots.entry(key.clone()).or_insert(Slot {
+                memo: None,
+                active: false,
+            });
+            if slot.active {
+                return Err(Cycle);
+            }
+            match &slot.memo {
+                Some(memo) if memo.verified_at == db.revision => return Ok(memo.changed_at),
+                Some(memo) => Some((Rc::clone(&memo.deps), memo.verified_at)),
+                None => None,
+            }
+        };
+
+        self.set_active(key, true);
+        let result = {
+            let _inactive = Defer(|| self.set_active(key, false));
+            self.recompute(db, key, previous)
+        };
+        let (value, deps) = match result? {
+            Recomputed::No => {
+                let mut slots = self.slots.borrow_mut();
+                let memo = slots
+                    .get_mut(key)
+                    .and_then(|slot| slot.memo.as_mut())
+                    .expect("verified");
+                memo.verified_at = db.revision;
+                return Ok(memo.changed_at);
+            }
+            Recomputed::Yes(value, deps) => (value, deps),
+        };
+
+        let mut slots = self.slots.borrow_mut();
+        let slot = slots.get_mut(key).expect("slot was inserted");
+        let changed_at = match &slot.memo {
+            // Early cutoff: dependents see no change.
+            Some(old) if old.value == value => old.changed_at,
+            _ => db.revision,
+        };
+        slot.memo = Some(Memo {
+            value,
+            deps: deps.into(),
+            verified_at: db.revision,
+            changed_at,
+        });
+        Ok(changed_at)
+    }
+
+    /// Recompute the value, unless `previous` shows that none of its dependencies changed.
+    fn recompute(
+        &self,
+        db: &Database,
+        key: &K,
+        previous: Option<(Rc<[Dep]>, u64)>,
+    ) -> Result<Recomputed<V>, Cycle> {
+        if let Some((deps, verified_at)) = previous {
+            let mut unchanged = true;
+            for dep in deps.iter() {
+                let changed = match dep {
+                    Dep::Input { id, tick } => db.inputs[*id].changed_since(*tick),
+                    Dep::Query { id, key } => {
+                        Rc::clone(&db.queries[*id]).verify(db, &**key)? > verified_at
+                    }
+                };
+                if changed {
+                    unchanged = false;
+                    break;
+                }
+            }
+            if unchanged {
+                return Ok(Recomputed::No);
+            }
+        }
+
+        db.stack.borrow_mut().push(Vec::new());
+        let _pop = Defer(|| {
+            db.stack.borrow_mut().pop();
+        });
+        let result = (self.f)(db, key);
+        let deps = std::mem::take(db.stack.borrow_mut().last_mut().expect("pushed above"));
+        Ok(Recomputed::Yes(result?, deps))
+    }
+
+    fn set_active(&self, key: &K, active: bool) {
+        if let Some(slot) = self.slots.borrow_mut().get_mut(key) {
+            slot.active = active;
+        }
+    }
+}
+
+/// Runs a closure when dropped, so bookkeeping is undone even if a query panics.
+struct Defer<F: FnMut()>(F);
+
+impl<F: FnMut()> Drop for Defer<F> {
+    fn drop(&mut self) {
+        (self.0)()
+    }
+}
+
+enum Recomputed<V> {
+    No,
+    Yes(V, Vec<Dep>),
+}
+
+impl<K, V> Storage for QueryStorage<K, V>
+where
+    K: Clone + Eq + Hash + 'static,
+    V: Clone + PartialEq + 'static,
+{
+    fn verify(&self, db: &Database, key: &dyn Any) -> Result<u64, Cycle> {
+        self.refresh(db, key.downcast_ref().expect("key of this query"))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cycle, Database, Query};
+    use std::cell::{Cell, RefCell};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    fn runs() -> (Rc<Cell<u32>>, Rc<Cell<u32>>) {
+        let runs = Rc::new(Cell::new(0));
+        (Rc::clone(&runs), runs)
+    }
+
+    #[test]
+    fn early_cutoff() {
+        let mut db = Database::new();
+        let text = db.input(String::from("a b"));
+
+        let (words_runs, counter) = runs();
+        let words = db.query(move |db, _: &()| {
+            counter.set(counter.get() + 1);
+            Ok(db.read(text).split_whitespace().count())
+        });
+        let (report_runs, counter) = runs();
+        let report = db.query(move |db, _: &()| {
+            counter.set(counter.get() + 1);
+            Ok(format!("{} words", db.get(words, &())?))
+        });
+
+        assert_eq!(db.get(report, &()).unwrap(), "2 words");
+        // Same word count, so `report` is reused.
+        db.set(text, String::from("c d"));
+        assert_eq!(db.get(report, &()).unwrap(), "2 words");
+        assert_eq!((words_runs.get(), report_runs.get()), (2, 1));
+
+        db.set(text, String::from("c d e"));
+        assert_eq!(db.get(report, &()).unwrap(), "3 words");
+        assert_eq!((words_runs.get(), report_runs.get()), (3, 2));
+    }
+
+    #[test]
+    fn keys_are_memoized_separately() {
+        let mut db = Database::new();
+        let scale = db.input(2);
+        let (runs, counter) = runs();
+        let scaled = db.query(move |db, n: &i32| {
+            counter.set(counter.get() + 1);
+            Ok(n * db.read(scale))
+        });
+        assert_eq!(db.get(scaled, &1), Ok(2));
+        assert_eq!(db.get(scaled, &2), Ok(4));
+        assert_eq!(db.get(scaled, &1), Ok(2));
+        assert_eq!(runs.get(), 2);
+    }
+
+    #[test]
+    fn cycles_are_errors() {
+        let mut db = Database::new();
+        let closes_loop = db.input(false);
+        // The query needs a handle to itself, which only exists once it is created.
+        let this: Rc<RefCell<Option<Query<u32, u32>>>> = Rc::new(RefCell::new(None));
+        let handle = Rc::clone(&this);
+        let step = db.query(move |db, &n: &u32| {
+            let step = handle.borrow().expect("set below");
+            match n {
+                0 if *db.read(closes_loop) => db.get(step, &2),
+                0 => Ok(0),
+                n => Ok(db.get(step, &(n - 1))? + 1),
+            }
+        });
+        *this.borrow_mut() = Some(step);
+
+        assert_eq!(db.get(step, &2), Ok(2));
+        db.set(closes_loop, true);
+        assert_eq!(db.get(step, &2), Err(Cycle));
+        // The database is still usable once the cycle is gone.
+        db.set(closes_loop, false);
+        assert_eq!(db.get(step, &2), Ok(2));
+    }
+
+    #[test]
+    fn panicking_queries_can_run_again() {
+        let mut db = Database::new();
+        let boom = db.input(true);
+        let checked = db.query(move |db, _: &()| {
+            assert!(!*db.read(boom), "boom");
+            Ok(1)
+        });
+        let outer = db.query(move |db, _: &()| db.get(checked, &()));
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| db.get(outer, &())));
+        assert!(result.is_err());
+        db.set(boom, false);
+        assert_eq!(db.get(outer, &()), Ok(1));
+    }
+}
@@ -0,0 +1,577 @@
+//! Fine-grained reactivity: [`Signal`], [`Memo`] and [`Effect`].
+
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+
+use crate::Cd;
+
+/// Something that reruns or recomputes when a value it read changes.
+trait Subscriber {
+    /// Bumped on every run, so subscriptions from older runs can be told apart.
+    fn epoch(&self) -> u64;
+
+    /// A value read during the run `epoch()` changed.
+    fn notify(&self);
+}
+
+type Observer = (Weak<dyn Subscriber>, u64);
+
+thread_local! {
+    /// The memo or effect that is running, which reads are recorded against.
+    static OBSERVER: RefCell<Option<Observer>> = const { RefCell::new(None) };
+    static PENDING: RefCell<Vec<Weak<EffectInner>>> = const { RefCell::new(Vec::new()) };
+    static BATCH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Run `f` with `observer` recording the signals and memos it reads.
+fn observe<R>(observer: Option<Observer>, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Observer>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            OBSERVER.with(|o| *o.borrow_mut() = self.0.take());
+        }
+    }
+    let _restore = Restore(OBSERVER.with(|o| o.replace(observer)));
+    f()
+}
+
+/// Run `f` without recording what it reads, even inside a memo or effect.
+/// ```
+/// use changed::reactive::{untrack, Effect, Signal};
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// let (a, b) = (Signal::new(1), Signal::new(1));
+/// let runs = Rc::new(Cell::new(0));
+/// let counter = Rc::clone(&runs);
+/// let (ea, eb) = (a.clone(), b.clone());
+/// let _effect = Effect::new(move || {
+///     counter.set(counter.get() + 1);
+///     let _ = ea.get() + untrack(|| eb.get());
+/// });
+///
+/// b.set(2);
+/// assert_eq!(runs.get(), 1);
+/// a.set(2);
+/// assert_eq!(runs.get(), 2);
+/// ```
+pub fn untrack<R>(f: impl FnOnce() -> R) -> R {
+    observe(None, f)
+}
+
+/// Run `f`, holding back effects until it returns so they run once for all of its writes.
+/// ```
+/// use changed::reactive::{batch, Effect, Signal};
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// let (a, b) = (Signal::new(1), Signal::new(1));
+/// let runs = Rc::new(Cell::new(0));
+/// let counter = Rc::clone(&runs);
+/// let (ea, eb) = (a.clone(), b.clone());
+/// let _effect = Effect::new(move || {
+///     counter.set(counter.get() + 1);
+///     let _ = ea.get() + eb.get();
+/// });
+///
+/// batch(|| {
+///     a.set(2);
+///     b.set(2);
+/// });
+/// assert_eq!(runs.get(), 2);
+/// ```
+pub fn batch<R>(f: impl FnOnce() -> R) -> R {
+    let out = {
+        let _batching = Batching::enter();
+        f()
+    };
+    flush();
+    out
+}
+
+/// Holds back effects while alive, even if the code it guards panics.
+struct Batching;
+
+impl Batching {
+    fn enter() -> Batching {
+        BATCH.with(|b| b.set(b.get() + 1));
+        Batching
+    }
+}
+
+impl Drop for Batching {
+    fn drop(&mut self) {
+        BATCH.with(|b| b.set(b.get() - 1));
+    }
+}
+
+/// Run pending effects, unless a batch or another flush is in progress.
+fn flush() {
+    if BATCH.with(Cell::get) > 0 {
+        return;
+    }
+    let _batching = Batching::enter();
+    while let Some(effect) = PENDING.with(|p| p.borrow_mut().pop()) {
+        if let Some(effect) = effect.upgrade() {
+            effect.run();
+        }
+    }
+}
+
+/// The memos and effects that read a value.
+#[derive(Default)]
+struct Subscribers(RefCell<Vec<Observer>>);
+
+impl Subscribers {
+    /// Record a read by the running memo or effect, if there is one.
+    ///
+    /// Subscriptions from older runs, and from dropped subscribers, are pruned on the way,
+    /// so a value that is read often but rarely written does not pile them up.
+    fn track(&self) {
+        OBSERVER.with(|o| {
+            if let Some((observer, epoch)) = &*o.borrow() {
+                let mut list = self.0.borrow_mut();
+                let mut known = false;
+                list.retain(|(s, e)| {
+                    if Weak::ptr_eq(s, observer) {
+                        known |= e == epoch;
+                        e == epoch
+                    } else {
+                        s.upgrade().is_some_and(|s| s.epoch() == *e)
+                    }
+                });
+                if !known {
+                    list.push((observer.clone(), *epoch));
+                }
+            }
+        })
+    }
+
+    /// Notify every subscriber whose latest run read the value.
+    /// They subscribe again when they next read it.
+    fn notify(&self) {
+        let list = self.0.take();
+        for (subscriber, epoch) in list {
+            if let Some(subscriber) = subscriber.upgrade() {
+                if subscriber.epoch() == epoch {
+                    subscriber.notify();
+                }
+            }
+        }
+    }
+}
+
+/// A [`Cd`] that memos and effects depend on when they read it.
+///
+/// Cloning a `Signal` gives another handle to the same value.
+///
+/// ```
+/// use changed::reactive::{Effect, Memo, Signal};
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+///
+/// let name = Signal::new("Ferris".to_string());
+/// let shout = {
+///     let name = name.clone();
+///     Memo::new(move || name.with(|n| n.to_uppercase()))
+/// };
+///
+/// let log = Rc::new(RefCell::new(Vec::new()));
+/// let sink = Rc::clone(&log);
+/// let memo = shout.clone();
+/// let _effect = Effect::new(move || sink.borrow_mut().push(memo.get()));
+///
+/// name.update(|n| n.push('!'));
+/// assert_eq!(*log.borrow(), ["FERRIS", "FERRIS!"]);
+/// ```
+pub struct Signal<T> {
+    inner: Rc<SignalInner<T>>,
+}
+
+struct SignalInner<T> {
+    cd: RefCell<Cd<T>>,
+    subscribers: Subscribers,
+}
+
+impl<T> Signal<T> {
+    /// Create a new Signal with data.
+    /// It is initialized to false for change detection.
+    pub fn new(data: T) -> Signal<T> {
+        Signal {
+            inner: Rc::new(SignalInner {
+                cd: RefCell::new(Cd::new(data)),
+                subscribers: Subscribers::default(),
+            }),
+        }
+    }
+
+    /// Read the data, recording the read in the running memo or effect.
+    pub fn get(&self) -> T
+    where
+        T: Clone,
+    {
+        self.with(T::clone)
+    }
+
+    /// Read the data by reference, recording the read in the running memo or effect.
+    ///
+    /// The data is borrowed while `f` runs, so `f` panics if it writes this Signal.
+    /// Use [`get()`](Signal::get()) to read a copy first.
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        self.inner.subscribers.track();
+        f(&self.inner.cd.borrow())
+    }
+
+    /// Replace the data, then rerun whatever read it.
+    pub fn set(&self, data: T) {
+        self.update(|d| *d = data);
+    }
+
+    /// Mutate the data, then rerun whatever read it.
+    ///
+    /// The data is borrowed mutably while `f` runs, so `f` panics if it reads or writes
+    /// this Signal. Use the `&mut T` it is given instead.
+    /// ```
+    /// use changed::reactive::Signal;
+    /// let count = Signal::new(1);
+    /// count.update(|c| *c += 1);
+    /// assert_eq!(count.get(), 2);
+    /// assert!(count.changed());
+    /// ```
+    pub fn update(&self, f: impl FnOnce(&mut T)) {
+        f(&mut self.inner.cd.borrow_mut());
+        self.inner.subscribers.notify();
+        flush();
+    }
+
+    /// Check if the Signal has been changed since the last call to reset (or created.)
+    /// Does not record a read.
+    pub fn changed(&self) -> bool {
+        self.inner.cd.borrow().changed()
+    }
+
+    /// Reset the change tracking to false. Does not rerun anything.
+    pub fn reset(&self) {
+        self.inner.cd.borrow_mut().reset();
+    }
+}
+
+impl<T> Clone for Signal<T> {
+    fn clone(&self) -> Self {
+        Signal {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
+
+/// Impl default where the data impls default. Change detection is initialized to false.
+impl<T: Default> Default for Signal<T> {
+    fn default() -> Self {
+        Signal::new(T::default())
+    }
+}
+
+/// A derived value that is recomputed on read, and only if a signal or memo it read has changed.
+///
+/// Cloning a `Memo` gives another handle to the same value.
+///
+/// ```
+/// use changed::reactive::{Memo, Signal};
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// let count = Signal::new(2);
+/// let runs = Rc::new(Cell::new(0));
+/// let counter = Rc::clone(&runs);
+/// let source = count.clone();
+/// let squared = Memo::new(move || {
+///     counter.set(counter.get() + 1);
+///     source.get() * source.get()
+/// });
+///
+/// assert_eq!(squared.get(), 4);
+/// assert_eq!(squared.get(), 4);
+/// assert_eq!(runs.get(), 1);
+///
+/// count.set(3);
+/// assert_eq!(squared.get(), 9);
+/// assert_eq!(runs.get(), 2);
+/// ```
+pub struct Memo<T> {
+    inner: Rc<MemoInner<T>>,
+}
+
+struct MemoInner<T> {
+    compute: RefCell<Box<dyn FnMut() -> T>>,
+    value: RefCell<Option<Rc<T>>>,
+    epoch: Cell<u64>,
+    subscribers: Subscribers,
+}
+
+impl<T: 'static> Memo<T> {
+    /// Create a Memo. `compute` first runs on the first read.
+    pub fn new(compute: impl FnMut() -> T + 'static) -> Memo<T> {
+        Memo {
+            inner: Rc::new(MemoInner {
+                compute: RefCell::new(Box::new(compute)),
+                value: RefCell::new(None),
+                epoch: Cell::new(0),
+                subscribers: Subscribers::default(),
+            }),
+        }
+    }
+
+    /// Read the value, recording the read in the running memo or effect.
+    pub fn get(&self) -> T
+    where
+        T: Clone,
+    {
+        self.with(T::clone)
+    }
+
+    /// Read the value by reference, recording the read in the running memo or effect.
+    ///
+    /// The memo is not borrowed while `f` runs, so `f` may write what the memo read.
+    /// It is then recomputed on the next read.
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let cached = self.inner.value.borrow().clone();
+        let value = match cached {
+            Some(value) => value,
+            None => {
+                let epoch = self.inner.epoch.get() + 1;
+                self.inner.epoch.set(epoch);
+                let observer: Weak<dyn Subscriber> = Rc::downgrade(&self.inner) as _;
+                let value = Rc::new(observe(Some((observer, epoch)), || {
+                    (self.inner.compute.borrow_mut())()
+                }));
+                *self.inner.value.borrow_mut() = Some(Rc::clone(&value));
+                value
+            }
+        };
+        self.inner.subscribers.track();
+        f(&value)
+    }
+}
+
+impl<T> Subscriber for MemoInner<T> {
+    fn epoch(&self) -> u64 {
+        self.epoch.get()
+    }
+
+    fn notify(&self) {
+        *self.value.borrow_mut() = None;
+        self.subscribers.notify();
+    }
+}
+
+impl<T> Clone for Memo<T> {
+    fn clone(&self) -> Self {
+        Memo {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
+
+/// A closure that runs once right away, then again whenever a signal or memo it read changes.
+///
+/// Dependencies are recorded afresh on every run, so a branch that is not taken does not
+/// rerun the effect. Dropping the `Effect` stops it.
+///
+/// ```
+/// use changed::reactive::{Effect, Signal};
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// let count = Signal::new(0);
+/// let seen = Rc::new(Cell::new(-1));
+/// let (source, sink) = (count.clone(), Rc::clone(&seen));
+/// let effect = Effect::new(move || sink.set(source.get()));
+/// assert_eq!(seen.get(), 0);
+///
+/// count.set(1);
+/// assert_eq!(seen.get(), 1);
+///
+/// drop(effect);
+/// count.set(2);
+/// assert_eq!(seen.get(), 1);
+/// ```
+pub struct Effect {
+    // Subscriptions only hold weak references, so this keeps the effect alive.
+    _inner: Rc<EffectInner>,
+}
+
+struct EffectInner {
+    f: RefCell<Box<dyn FnMut()>>,
+    epoch: Cell<u64>,
+    this: Weak<EffectInner>,
+}
+
+impl Effect {
+    /// Create an Effect, running `f` right away.
+    pub fn new(f: impl FnMut() + 'static) -> Effect {
+        let inner = Rc::new_cyclic(|this| EffectInner {
+            f: RefCell::new(Box::new(f)),
+            epoch: Cell::new(0),
+            this: this.clone(),
+        });
+        // Writes from the first run are batched like writes from later runs,
+        // so the effect never reruns while it is still running.
+        batch(|| inner.run());
+        Effect { _inner: inner }
+    }
+}
+
+impl EffectInner {
+    fn run(&self) {
+        let epoch = self.epoch.get() + 1;
+        self.epoch.set(epoch);
+        let observer: Weak<dyn Subscriber> = self.this.clone();
+        observe(Some((observer, epoch)), || (self.f.borrow_mut())());
+    }
+}
+
+impl Subscriber for EffectInner {
+    fn epoch(&self) -> u64 {
+        self.epoch.get()
+    }
+
+    fn notify(&self) {
+        PENDING.with(|p| {
+            let mut pending = p.borrow_mut();
+            if !pending.iter().any(|e| Weak::ptr_eq(e, &self.this)) {
+                pending.push(self.this.clone());
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{batch, Effect, Memo, Signal};
+    use std::cell::{Cell, RefCell};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    #[test]
+    fn dependencies_follow_branches() {
+        let flag = Signal::new(true);
+        let (a, b) = (Signal::new(1), Signal::new(10));
+        let runs = Rc::new(Cell::new(0));
+
+        let (f, ea, eb, counter) = (flag.clone(), a.clone(), b.clone(), Rc::clone(&runs));
+        let _effect = Effect::new(move || {
+            counter.set(counter.get() + 1);
+            if f.get() {
+                ea.get();
+            } else {
+                eb.get();
+            }
+        });
+        assert_eq!(runs.get(), 1);
+
+        b.set(11);
+        assert_eq!(runs.get(), 1);
+        flag.set(false);
+        assert_eq!(runs.get(), 2);
+        // `a` is no longer read.
+        a.set(2);
+        assert_eq!(runs.get(), 2);
+        b.set(12);
+        assert_eq!(runs.get(), 3);
+    }
+
+    #[test]
+    fn memos_chain() {
+        let base = Signal::new(1);
+        let source = base.clone();
+        let double = Memo::new(move || source.get() * 2);
+        let inner = double.clone();
+        let plus_one = Memo::new(move || inner.get() + 1);
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let (sink, memo) = (Rc::clone(&log), plus_one.clone());
+        let _effect = Effect::new(move || sink.borrow_mut().push(memo.get()));
+
+        base.set(2);
+        base.set(3);
+        assert_eq!(*log.borrow(), [3, 5, 7]);
+        assert_eq!(double.get(), 6);
+    }
+
+    #[test]
+    fn effects_can_write_signals() {
+        let input = Signal::new(1);
+        let output = Signal::new(0);
+        let (i, o) = (input.clone(), output.clone());
+        let _copy = Effect::new(move || o.set(i.get() * 10));
+
+        let seen = Rc::new(Cell::new(0));
+        let (o, sink) = (output.clone(), Rc::clone(&seen));
+        let _watch = Effect::new(move || sink.set(o.get()));
+
+        input.set(2);
+        assert_eq!(seen.get(), 20);
+        assert!(output.changed());
+    }
+
+    #[test]
+    fn effects_can_write_what_they_read() {
+        let count = Signal::new(0);
+        let source = count.clone();
+        let _clamp = Effect::new(move || {
+            if source.get() < 3 {
+                source.update(|c| *c += 1);
+            }
+        });
+        assert_eq!(count.get(), 3);
+    }
+
+    #[test]
+    fn reruns_replace_old_subscriptions() {
+        let (a, b) = (Signal::new(0), Signal::new(0));
+        let (ea, eb) = (a.clone(), b.clone());
+        let _effect = Effect::new(move || {
+            let _ = ea.get() + eb.get();
+        });
+        for i in 1..100 {
+            b.set(i);
+        }
+        assert_eq!(a.inner.subscribers.0.borrow().len(), 1);
+    }
+
+    #[test]
+    fn panics_do_not_stall_effects() {
+        let count = Signal::new(0);
+        let seen = Rc::new(Cell::new(0));
+        let (source, sink) = (count.clone(), Rc::clone(&seen));
+        let _effect = Effect::new(move || sink.set(source.get()));
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            batch(|| {
+                count.set(1);
+                panic!("in batch");
+            })
+        }));
+        assert!(result.is_err());
+
+        count.set(2);
+        assert_eq!(seen.get(), 2);
+    }
+    #[test]
+    fn memos_can_be_read_while_their_inputs_change() {
+        let count = Signal::new(1);
+        let source = count.clone();
+        let double = Memo::new(move || source.get() * 2);
+
+        double.with(|d| count.set(*d));
+        assert_eq!(count.get(), 2);
+        assert_eq!(double.get(), 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn signals_cannot_be_written_while_read() {
+        let count = Signal::new(1);
+        count.with(|c| count.set(*c + 1));
+    }
+}
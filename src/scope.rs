@@ -0,0 +1,154 @@
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use crate::AnyChanged;
+
+/// ChangeScope: resets a group of trackers together, at the end of a frame
+///
+/// Trackers are enrolled through weak handles to their `Rc<RefCell<_>>`, so the scope does not keep
+/// them alive. Dropped trackers are forgotten at the next [`end_frame()`](ChangeScope::end_frame()).
+///
+/// ```
+/// use changed::{Cd, ChangeScope};
+/// use std::{cell::RefCell, rc::Rc};
+///
+/// let position = Rc::new(RefCell::new(Cd::new((0, 0))));
+/// let health = Rc::new(RefCell::new(Cd::new(10)));
+///
+/// let mut scope = ChangeScope::new();
+/// scope.enroll_labeled(&position, "position");
+/// scope.enroll(&health);
+///
+/// position.borrow_mut().0 += 1;
+/// **health.borrow_mut() -= 1;
+/// assert_eq!(scope.changes().collect::<Vec<_>>(), [Some("position"), None]);
+///
+/// scope.end_frame();
+/// assert!(!position.borrow().changed());
+/// assert!(!scope.changed());
+/// ```
+#[derive(Default)]
+pub struct ChangeScope {
+    entries: Vec<Enrolled>,
+}
+
+struct Enrolled {
+    tracked: Weak<RefCell<dyn AnyChanged>>,
+    label: Option<String>,
+}
+
+impl ChangeScope {
+    /// Create an empty ChangeScope.
+    pub fn new() -> ChangeScope {
+        ChangeScope::default()
+    }
+
+    /// Enroll a tracker, to be reset by [`end_frame()`](ChangeScope::end_frame()).
+    pub fn enroll<T: AnyChanged + 'static>(&mut self, tracked: &Rc<RefCell<T>>) {
+        self.push(tracked, None);
+    }
+
+    /// Enroll a tracker with a label, reported by [`changes()`](ChangeScope::changes()).
+    pub fn enroll_labeled<T: AnyChanged + 'static>(
+        &mut self,
+        tracked: &Rc<RefCell<T>>,
+        label: impl Into<String>,
+    ) {
+        self.push(tracked, Some(label.into()));
+    }
+
+    fn push<T: AnyChanged + 'static>(&mut self, tracked: &Rc<RefCell<T>>, label: Option<String>) {
+        let tracked: Rc<RefCell<dyn AnyChanged>> = tracked.clone();
+        self.entries.push(Enrolled {
+            tracked: Rc::downgrade(&tracked),
+            label,
+        });
+    }
+
+    /// Check if any enrolled tracker still alive has been changed since the last frame.
+    ///
+    /// Panics if one of them is mutably borrowed.
+    pub fn changed(&self) -> bool {
+        self.changes().next().is_some()
+    }
+
+    /// The label of every enrolled tracker that has been changed since the last frame,
+    /// or `None` for those enrolled without one, in the order they were enrolled.
+    ///
+    /// Panics if one of them is mutably borrowed.
+    pub fn changes(&self) -> impl Iterator<Item = Option<&str>> {
+        self.entries.iter().filter_map(|entry| {
+            let tracked = entry.tracked.upgrade()?;
+            let changed = tracked.borrow().changed();
+            changed.then_some(entry.label.as_deref())
+        })
+    }
+
+    /// The number of enrolled trackers, including those dropped since the last frame.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if nothing is enrolled.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Reset the change tracking of every enrolled tracker to false,
+    /// and forget the ones that have been dropped.
+    ///
+    /// Panics if one of them is borrowed.
+    /// ```
+    /// use changed::{Cd, ChangeScope};
+    /// use std::{cell::RefCell, rc::Rc};
+    ///
+    /// let mut scope = ChangeScope::new();
+    /// let kept = Rc::new(RefCell::new(Cd::new_true(1)));
+    /// scope.enroll(&kept);
+    /// scope.enroll(&Rc::new(RefCell::new(Cd::new_true(2))));
+    /// assert_eq!(scope.len(), 2);
+    ///
+    /// scope.end_frame();
+    /// assert_eq!(scope.len(), 1);
+    /// assert!(!kept.borrow().changed());
+    /// ```
+    pub fn end_frame(&mut self) {
+        self.entries.retain(|entry| match entry.tracked.upgrade() {
+            Some(tracked) => {
+                tracked.borrow_mut().reset();
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::{Cd, CdVec, ChangeScope};
+
+    #[test]
+    fn mixed_trackers_and_groups() {
+        let list = Rc::new(RefCell::new(CdVec::from(vec![1, 2, 3])));
+        let pair = Rc::new(RefCell::new((Cd::new('a'), Cd::new('b'))));
+
+        let mut scope = ChangeScope::new();
+        scope.enroll_labeled(&list, "list");
+        scope.enroll_labeled(&pair, String::from("pair"));
+        assert!(!scope.changed());
+
+        *pair.borrow_mut().1 = 'c';
+        assert_eq!(scope.changes().collect::<Vec<_>>(), [Some("pair")]);
+
+        list.borrow_mut().push(4);
+        drop(pair);
+        assert_eq!(scope.changes().collect::<Vec<_>>(), [Some("list")]);
+
+        scope.end_frame();
+        assert_eq!(scope.len(), 1);
+        assert!(!list.borrow().changed());
+    }
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Cd, ChangeDetector};
+
+/// Serializes as the data alone, without the change tracking.
+/// Use [`with_flag`] to keep it.
+/// ```
+/// use changed::Cd;
+/// let cd = Cd::new_true(5);
+/// assert_eq!(serde_json::to_string(&cd).unwrap(), "5");
+/// ```
+impl<T: Serialize, D> Serialize for Cd<T, D> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.data.serialize(serializer)
+    }
+}
+
+/// Deserializes from the data alone. Change detection is initialized to false.
+/// ```
+/// use changed::Cd;
+/// let cd: Cd<i32> = serde_json::from_str("5").unwrap();
+/// assert_eq!(*cd, 5);
+/// assert!(!cd.changed());
+/// ```
+impl<'de, T: Deserialize<'de>, D: ChangeDetector<T> + Default> Deserialize<'de> for Cd<T, D> {
+    fn deserialize<De: Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        T::deserialize(deserializer).map(|data| Cd::with_detector(data, D::default()))
+    }
+}
+
+/// Serialize a [`Cd`] along with whether it has changed, for `#[serde(with = "changed::with_flag")]`.
+///
+/// The detector itself is not serialized: a `Cd` that had changed deserializes
+/// as if created with [`with_detector_true()`](Cd::with_detector_true()).
+///
+/// ```
+/// use changed::Cd;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Form {
+///     #[serde(with = "changed::with_flag")]
+///     name: Cd<String>,
+/// }
+///
+/// let mut form = Form { name: Cd::new("Ferris".to_string()) };
+/// form.name.push('!');
+///
+/// let json = serde_json::to_string(&form).unwrap();
+/// assert_eq!(json, r#"{"name":{"data":"Ferris!","changed":true}}"#);
+/// let form: Form = serde_json::from_str(&json).unwrap();
+/// assert!(form.name.changed());
+/// ```
+pub mod with_flag {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::{Cd, ChangeDetector};
+
+    #[derive(Serialize)]
+    struct Flagged<'a, T> {
+        data: &'a T,
+        changed: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct OwnedFlagged<T> {
+        data: T,
+        changed: bool,
+    }
+
+    /// Serialize `cd` as `{ data, changed }`.
+    pub fn serialize<T, D, S>(cd: &Cd<T, D>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        D: ChangeDetector<T>,
+        S: Serializer,
+    {
+        Flagged {
+            data: &**cd,
+            changed: cd.changed(),
+        }
+        .serialize(serializer)
+    }
+
+    /// Deserialize a `Cd` from `{ data, changed }`.
+    pub fn deserialize<'de, T, D, De>(deserializer: De) -> Result<Cd<T, D>, De::Error>
+    where
+        T: Deserialize<'de>,
+        D: ChangeDetector<T> + Default,
+        De: Deserializer<'de>,
+    {
+        let OwnedFlagged { data, changed } = OwnedFlagged::deserialize(deserializer)?;
+        Ok(if changed {
+            Cd::with_detector_true(data, D::default())
+        } else {
+            Cd::with_detector(data, D::default())
+        })
+    }
+}
@@ -0,0 +1,313 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeSet, HashSet};
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+/// A set that can back a [`CdSet`]. Implemented for `HashSet` and `BTreeSet`.
+pub trait SetBackend<T>: Default + SetLookup<T, T> {
+    /// See `HashSet::insert`.
+    fn insert(&mut self, value: T) -> bool;
+    /// See `HashSet::is_empty`.
+    fn is_empty(&self) -> bool;
+    /// See `HashSet::iter`.
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a T> + 'a>;
+}
+
+/// Lookups in a [`SetBackend`] by a borrowed form `Q` of the element,
+/// like `HashSet::contains` taking any `&Q` where `T: Borrow<Q>`.
+pub trait SetLookup<Q: ?Sized, T> {
+    /// See `HashSet::contains`.
+    fn contains(&self, value: &Q) -> bool;
+    /// See `HashSet::remove`.
+    fn remove(&mut self, value: &Q) -> bool;
+    /// See `HashSet::take`.
+    fn take(&mut self, value: &Q) -> Option<T>;
+}
+
+impl<T: Hash + Eq, S: BuildHasher + Default> SetBackend<T> for HashSet<T, S> {
+    fn insert(&mut self, value: T) -> bool {
+        HashSet::insert(self, value)
+    }
+
+    fn is_empty(&self) -> bool {
+        HashSet::is_empty(self)
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a T> + 'a> {
+        Box::new(HashSet::iter(self))
+    }
+}
+
+impl<Q, T, S> SetLookup<Q, T> for HashSet<T, S>
+where
+    Q: Hash + Eq + ?Sized,
+    T: Hash + Eq + Borrow<Q>,
+    S: BuildHasher,
+{
+    fn contains(&self, value: &Q) -> bool {
+        HashSet::contains(self, value)
+    }
+
+    fn remove(&mut self, value: &Q) -> bool {
+        HashSet::remove(self, value)
+    }
+
+    fn take(&mut self, value: &Q) -> Option<T> {
+        HashSet::take(self, value)
+    }
+}
+
+impl<T: Ord> SetBackend<T> for BTreeSet<T> {
+    fn insert(&mut self, value: T) -> bool {
+        BTreeSet::insert(self, value)
+    }
+
+    fn is_empty(&self) -> bool {
+        BTreeSet::is_empty(self)
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a T> + 'a> {
+        Box::new(BTreeSet::iter(self))
+    }
+}
+
+impl<Q, T> SetLookup<Q, T> for BTreeSet<T>
+where
+    Q: Ord + ?Sized,
+    T: Ord + Borrow<Q>,
+{
+    fn contains(&self, value: &Q) -> bool {
+        BTreeSet::contains(self, value)
+    }
+
+    fn remove(&mut self, value: &Q) -> bool {
+        BTreeSet::remove(self, value)
+    }
+
+    fn take(&mut self, value: &Q) -> Option<T> {
+        BTreeSet::take(self, value)
+    }
+}
+
+/// CdSet: Change Detection for set membership
+///
+/// Records which elements were [`added()`](CdSet::added()) and [`removed()`](CdSet::removed())
+/// since the last [`reset()`](CdSet::reset()). Adding and removing the same element
+/// within one cycle cancels out.
+///
+/// Backed by a `HashSet` by default, or any other [`SetBackend`] such as a `BTreeSet`.
+///
+/// ```
+/// use changed::CdSet;
+/// use std::collections::BTreeSet;
+///
+/// let mut region: CdSet<u32, BTreeSet<_>> = CdSet::new(BTreeSet::from([1, 2]));
+/// region.insert(3);
+/// region.remove(&1);
+/// region.insert(4);
+/// region.remove(&4);
+///
+/// assert_eq!(region.added(), &BTreeSet::from([3]));
+/// assert_eq!(region.removed(), &BTreeSet::from([1]));
+/// ```
+pub struct CdSet<T, S: SetBackend<T> = HashSet<T>> {
+    data: S,
+    added: S,
+    removed: S,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Clone, S: SetBackend<T>> CdSet<T, S> {
+    /// Create a new CdSet with data.
+    /// It is initialized to false for change detection.
+    ///
+    /// ```
+    /// use changed::CdSet;
+    /// use std::collections::HashSet;
+    /// let cd = CdSet::new(HashSet::from([1]));
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn new(data: S) -> CdSet<T, S> {
+        CdSet {
+            data,
+            added: S::default(),
+            removed: S::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Reset the change tracking to false.
+    /// ```
+    /// use changed::CdSet;
+    /// let mut cd: CdSet<i32> = CdSet::default();
+    /// cd.insert(1);
+    /// cd.reset();
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn reset(&mut self) {
+        self.added = S::default();
+        self.removed = S::default();
+    }
+
+    /// Take the data out of the CdSet.
+    /// Consumes self and returns data.
+    /// ```
+    /// use changed::CdSet;
+    /// let mut cd: CdSet<i32> = CdSet::default();
+    /// cd.insert(1);
+    /// assert!(cd.take().contains(&1));
+    /// ```
+    pub fn take(self) -> S {
+        self.data
+    }
+
+    /// Check if any element has been added or removed since the last call to reset (or created.)
+    /// ```
+    /// use changed::CdSet;
+    /// let mut cd: CdSet<i32> = CdSet::default();
+    /// cd.insert(1);
+    /// assert!(cd.changed());
+    /// ```
+    pub fn changed(&self) -> bool {
+        !self.added.is_empty() || !self.removed.is_empty()
+    }
+
+    /// The elements added since the last call to reset.
+    pub fn added(&self) -> &S {
+        &self.added
+    }
+
+    /// The elements removed since the last call to reset.
+    pub fn removed(&self) -> &S {
+        &self.removed
+    }
+
+    /// Mutate the CdSet without tripping change detection.
+    ///
+    /// ```
+    /// use changed::CdSet;
+    /// let mut cd: CdSet<i32> = CdSet::default();
+    /// cd.mutate_silently().insert(1);
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn mutate_silently(&mut self) -> &mut S {
+        &mut self.data
+    }
+
+    /// Add an element, recording it as added if it was not already in the set.
+    /// Returns whether it was newly inserted.
+    /// ```
+    /// use changed::CdSet;
+    /// let mut cd: CdSet<i32> = CdSet::default();
+    /// assert!(cd.insert(1));
+    /// assert!(cd.added().contains(&1));
+    /// ```
+    pub fn insert(&mut self, value: T) -> bool {
+        if self.data.contains(&value) {
+            return false;
+        }
+        if !self.removed.remove(&value) {
+            self.added.insert(value.clone());
+        }
+        self.data.insert(value)
+    }
+
+    /// Remove an element, recording it as removed if it was in the set.
+    /// Returns whether it was present.
+    ///
+    /// The element may be any borrowed form of `T`, like `HashSet::remove`.
+    /// ```
+    /// use changed::CdSet;
+    /// let mut cd: CdSet<String> = CdSet::default();
+    /// cd.mutate_silently().insert("a".to_string());
+    /// assert!(cd.remove("a"));
+    /// assert!(cd.removed().contains("a"));
+    /// ```
+    pub fn remove<Q: ?Sized>(&mut self, value: &Q) -> bool
+    where
+        S: SetLookup<Q, T>,
+    {
+        let Some(value) = SetLookup::<Q, T>::take(&mut self.data, value) else {
+            return false;
+        };
+        if !SetLookup::<T, T>::remove(&mut self.added, &value) {
+            self.removed.insert(value);
+        }
+        true
+    }
+
+    /// Remove every element, recording each as removed.
+    /// ```
+    /// use changed::CdSet;
+    /// let mut cd: CdSet<i32> = CdSet::default();
+    /// cd.mutate_silently().insert(1);
+    /// cd.insert(2);
+    /// cd.clear();
+    /// assert!(cd.added().is_empty());
+    /// assert!(cd.removed().contains(&1));
+    /// ```
+    pub fn clear(&mut self) {
+        let values: Vec<T> = self.data.iter().cloned().collect();
+        for value in &values {
+            self.remove(value);
+        }
+    }
+}
+
+/// deref does not trip change detection.
+/// ```
+/// use changed::CdSet;
+/// let mut cd: CdSet<i32> = CdSet::default();
+/// cd.insert(1);
+/// cd.reset();
+/// assert!(cd.contains(&1));
+/// assert!(!cd.changed());
+/// ```
+impl<T, S: SetBackend<T>> Deref for CdSet<T, S> {
+    type Target = S;
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+/// Impl default. Change detection is initialized to false.
+/// ```
+/// use changed::CdSet;
+/// let empty: CdSet<i32> = CdSet::default();
+/// assert!(!empty.changed());
+/// ```
+impl<T: Clone, S: SetBackend<T>> Default for CdSet<T, S> {
+    fn default() -> Self {
+        CdSet::new(S::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CdSet;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn membership_changes_cancel_out() {
+        let mut cd: CdSet<i32, BTreeSet<_>> = CdSet::new(BTreeSet::from([1, 2]));
+
+        // Removed, then back: no change.
+        cd.remove(&1);
+        cd.insert(1);
+        // Added, then gone: no change.
+        cd.insert(3);
+        cd.remove(&3);
+        // Redundant operations are not changes either.
+        cd.insert(2);
+        cd.remove(&4);
+        assert!(!cd.changed());
+
+        cd.remove(&2);
+        cd.insert(5);
+        assert_eq!(cd.added(), &BTreeSet::from([5]));
+        assert_eq!(cd.removed(), &BTreeSet::from([2]));
+        assert_eq!(cd.take(), BTreeSet::from([1, 5]));
+    }
+}
@@ -0,0 +1,371 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::PoisonError;
+use std::time::{Duration, Instant};
+
+#[cfg(loom)]
+use loom::sync::{
+    atomic::{AtomicBool, Ordering},
+    Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
+#[cfg(not(loom))]
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
+
+/// SyncCd: Change Detection that can be shared between threads
+///
+/// The data lives behind a lock, but the change flag is atomic, so
+/// [`changed()`](SyncCd::changed()) and [`reset()`](SyncCd::reset()) never wait for it.
+/// [`write()`](SyncCd::write()) guards trip change detection when they are dropped,
+/// and [`read()`](SyncCd::read()) guards do not.
+///
+/// ```
+/// use changed::SyncCd;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let cd = Arc::new(SyncCd::new(5));
+/// let writer = Arc::clone(&cd);
+/// thread::spawn(move || *writer.write() += 5).join().unwrap();
+///
+/// assert!(cd.changed());
+/// cd.reset();
+/// assert_eq!(*cd.read(), 10);
+/// ```
+///
+/// To never miss a change, reset before reading: a write that lands in between
+/// will be seen by the next call to `changed()`.
+///
+/// Threads can also block until a write happens, with [`wait_changed()`](SyncCd::wait_changed())
+/// and [`wait_until()`](SyncCd::wait_until()).
+pub struct SyncCd<T> {
+    data: RwLock<T>,
+    changed: AtomicBool,
+    signal: Signal,
+}
+
+/// Counts dirtying writes, so waiting threads can tell a new write from a spurious wakeup.
+struct Signal {
+    writes: Mutex<u64>,
+    condvar: Condvar,
+}
+
+impl Signal {
+    fn new() -> Signal {
+        Signal {
+            writes: Mutex::new(0),
+            condvar: Condvar::new(),
+        }
+    }
+
+    fn notify(&self) {
+        *self.writes.lock().unwrap_or_else(PoisonError::into_inner) += 1;
+        self.condvar.notify_all();
+    }
+}
+
+impl<T> SyncCd<T> {
+    /// Create a new SyncCd with data.
+    /// It is initialized to false for change detection.
+    ///
+    /// ```
+    /// use changed::SyncCd;
+    /// let cd = SyncCd::new(5);
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn new(data: T) -> SyncCd<T> {
+        SyncCd {
+            data: RwLock::new(data),
+            changed: AtomicBool::new(false),
+            signal: Signal::new(),
+        }
+    }
+
+    /// Create a new SyncCd with data.
+    /// It is initialized to true for change detection.
+    /// ```
+    /// use changed::SyncCd;
+    /// let cd = SyncCd::new_true(5);
+    /// assert!(cd.changed());
+    /// ```
+    pub fn new_true(data: T) -> SyncCd<T> {
+        SyncCd {
+            data: RwLock::new(data),
+            changed: AtomicBool::new(true),
+            signal: Signal::new(),
+        }
+    }
+
+    /// Reset the change tracking to false. Does not lock the data.
+    /// ```
+    /// use changed::SyncCd;
+    /// let cd = SyncCd::new_true(5);
+    /// cd.reset();
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn reset(&self) {
+        self.changed.store(false, Ordering::Release);
+    }
+
+    /// Check if the SyncCd has been written to since the last call to reset (or created.)
+    /// Does not lock the data.
+    /// ```
+    /// use changed::SyncCd;
+    /// let cd = SyncCd::new(5);
+    /// *cd.write() += 5;
+    /// assert!(cd.changed());
+    /// ```
+    pub fn changed(&self) -> bool {
+        self.changed.load(Ordering::Acquire)
+    }
+
+    /// Lock the data for reading. Does not trip change detection.
+    /// ```
+    /// use changed::SyncCd;
+    /// let cd = SyncCd::new(5);
+    /// assert_eq!(*cd.read(), 5);
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn read(&self) -> SyncReadGuard<'_, T> {
+        SyncReadGuard {
+            guard: self.data.read().unwrap_or_else(PoisonError::into_inner),
+        }
+    }
+
+    /// Lock the data for writing. Trips change detection when the guard is dropped.
+    /// ```
+    /// use changed::SyncCd;
+    /// let cd = SyncCd::new(5);
+    /// let mut guard = cd.write();
+    /// *guard += 5;
+    /// drop(guard);
+    /// assert!(cd.changed());
+    /// ```
+    pub fn write(&self) -> SyncWriteGuard<'_, T> {
+        SyncWriteGuard {
+            guard: self.data.write().unwrap_or_else(PoisonError::into_inner),
+            changed: Some((&self.changed, &self.signal)),
+        }
+    }
+
+    /// Lock the data for writing without tripping change detection.
+    /// ```
+    /// use changed::SyncCd;
+    /// let cd = SyncCd::new(5);
+    /// *cd.write_silently() += 5;
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn write_silently(&self) -> SyncWriteGuard<'_, T> {
+        SyncWriteGuard {
+            guard: self.data.write().unwrap_or_else(PoisonError::into_inner),
+            changed: None,
+        }
+    }
+
+    /// Block until the SyncCd has been written to since the last call to reset, or `timeout` passes.
+    /// Returns [`changed()`](SyncCd::changed()), so it returns true right away if there
+    /// is a change that has not been reset yet.
+    /// ```
+    /// use changed::SyncCd;
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let cd = Arc::new(SyncCd::new(5));
+    /// assert!(!cd.wait_changed(Duration::from_millis(1)));
+    ///
+    /// let writer = Arc::clone(&cd);
+    /// thread::spawn(move || *writer.write() += 1);
+    /// assert!(cd.wait_changed(Duration::from_secs(60)));
+    /// ```
+    pub fn wait_changed(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut writes = self
+            .signal
+            .writes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        while !self.changed() {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            writes = self
+                .signal
+                .condvar
+                .wait_timeout(writes, deadline - now)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+        true
+    }
+
+    /// Block until `predicate` holds for the data, checking again after every write
+    /// that trips change detection. Returns a read guard of the data that passed.
+    /// ```
+    /// use changed::SyncCd;
+    /// use std::sync::Arc;
+    /// use std::thread;
+    ///
+    /// let cd = Arc::new(SyncCd::new(0));
+    /// let writer = Arc::clone(&cd);
+    /// thread::spawn(move || {
+    ///     for _ in 0..10 {
+    ///         *writer.write() += 1;
+    ///     }
+    /// });
+    /// assert!(*cd.wait_until(|v| *v >= 5) >= 5);
+    /// ```
+    pub fn wait_until<F: FnMut(&T) -> bool>(&self, mut predicate: F) -> SyncReadGuard<'_, T> {
+        loop {
+            let seen = *self
+                .signal
+                .writes
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let guard = self.read();
+            if predicate(&guard) {
+                return guard;
+            }
+            drop(guard);
+
+            let mut writes = self
+                .signal
+                .writes
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            while *writes == seen {
+                writes = self
+                    .signal
+                    .condvar
+                    .wait(writes)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+        }
+    }
+
+    /// Take the data out of the SyncCd.
+    /// Consumes self and returns data.
+    /// ```
+    /// use changed::SyncCd;
+    /// let cd = SyncCd::new(5);
+    /// assert_eq!(cd.take(), 5);
+    /// ```
+    pub fn take(self) -> T {
+        self.data
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Impl default where the data impls default. Change detection is initialized to false.
+/// ```
+/// use changed::SyncCd;
+/// let zero: SyncCd<i32> = SyncCd::default();
+/// assert!(!zero.changed());
+/// ```
+impl<T: Default> Default for SyncCd<T> {
+    fn default() -> Self {
+        SyncCd::new(T::default())
+    }
+}
+
+/// Shared access to the data of a [`SyncCd`], from [`read()`](SyncCd::read()).
+pub struct SyncReadGuard<'a, T> {
+    guard: RwLockReadGuard<'a, T>,
+}
+
+impl<T> Deref for SyncReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+/// Exclusive access to the data of a [`SyncCd`], from [`write()`](SyncCd::write())
+/// or [`write_silently()`](SyncCd::write_silently()).
+pub struct SyncWriteGuard<'a, T> {
+    guard: RwLockWriteGuard<'a, T>,
+    changed: Option<(&'a AtomicBool, &'a Signal)>,
+}
+
+impl<T> Deref for SyncWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for SyncWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for SyncWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        // Still holding the lock, so anyone who sees the flag and then
+        // reads will see this write.
+        if let Some((changed, signal)) = self.changed {
+            changed.store(true, Ordering::Release);
+            signal.notify();
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use crate::SyncCd;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn writes_from_many_threads() {
+        let cd = Arc::new(SyncCd::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let cd = Arc::clone(&cd);
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        *cd.write() += 1;
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert!(cd.changed());
+        cd.reset();
+        assert_eq!(*cd.read(), 400);
+        assert!(!cd.changed());
+    }
+
+    #[test]
+    fn silent_writes_do_not_wake() {
+        let cd = Arc::new(SyncCd::new(0));
+        let writer = Arc::clone(&cd);
+        thread::spawn(move || *writer.write_silently() += 1)
+            .join()
+            .unwrap();
+        assert!(!cd.wait_changed(Duration::from_millis(10)));
+        assert_eq!(*cd.read(), 1);
+    }
+
+    #[test]
+    fn wait_until_checks_every_write() {
+        let cd = Arc::new(SyncCd::new(Vec::new()));
+        let writer = Arc::clone(&cd);
+        let thread = thread::spawn(move || {
+            for i in 0..100 {
+                writer.write().push(i);
+            }
+        });
+        assert_eq!(cd.wait_until(|v| v.len() == 100).last(), Some(&99));
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,270 @@
+use std::ops::{Index, IndexMut};
+
+use crate::Cd;
+
+/// A node of a [`CdTree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// CdTree: Change Detection for a tree
+///
+/// Every node keeps its data in a [`Cd`], and a "descendant dirty" bit that is set
+/// when any node below it changes. Mutating a node sets the bit on its ancestors,
+/// stopping at the first one that already has it, so a traversal with
+/// [`dirty_descendants()`](CdTree::dirty_descendants()) can skip clean branches.
+///
+/// ```
+/// use changed::CdTree;
+///
+/// let mut scene = CdTree::new("root");
+/// let arm = scene.add_child(scene.root(), "arm");
+/// let hand = scene.add_child(arm, "hand");
+/// let leg = scene.add_child(scene.root(), "leg");
+///
+/// *scene.get_mut(hand) = "claw";
+/// assert!(scene.changed(hand));
+/// assert!(!scene.changed(arm));
+/// assert!(scene.descendant_changed(arm));
+/// assert!(!scene.descendant_changed(leg));
+/// assert_eq!(scene.dirty_descendants(scene.root()).collect::<Vec<_>>(), [hand]);
+///
+/// scene.reset_subtree(arm);
+/// assert!(!scene.descendant_changed(scene.root()));
+/// ```
+pub struct CdTree<T> {
+    nodes: Vec<Node<T>>,
+}
+
+struct Node<T> {
+    cd: Cd<T>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    descendant_dirty: bool,
+}
+
+impl<T> CdTree<T> {
+    /// Create a new CdTree with data for its root.
+    /// It is initialized to false for change detection.
+    pub fn new(root: T) -> CdTree<T> {
+        CdTree {
+            nodes: vec![Node {
+                cd: Cd::new(root),
+                parent: None,
+                children: Vec::new(),
+                descendant_dirty: false,
+            }],
+        }
+    }
+
+    /// The root node.
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// Add a node under `parent`. It is initialized to false for change detection.
+    pub fn add_child(&mut self, parent: NodeId, data: T) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            cd: Cd::new(data),
+            parent: Some(parent),
+            children: Vec::new(),
+            descendant_dirty: false,
+        });
+        self.nodes[parent.0].children.push(id);
+        id
+    }
+
+    /// The parent of a node, or `None` for the root.
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].parent
+    }
+
+    /// The children of a node, in the order they were added.
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.nodes[id.0].children
+    }
+
+    /// Read the data of a node. Does not trip change detection.
+    pub fn get(&self, id: NodeId) -> &T {
+        &self.nodes[id.0].cd
+    }
+
+    /// Mutate the data of a node, marking it changed and its ancestors descendant-dirty.
+    pub fn get_mut(&mut self, id: NodeId) -> &mut T {
+        self.mark_ancestors(id);
+        &mut self.nodes[id.0].cd
+    }
+
+    /// Mutate the data of a node without tripping change detection.
+    pub fn mutate_silently(&mut self, id: NodeId) -> &mut T {
+        self.nodes[id.0].cd.mutate_silently()
+    }
+
+    /// Check if the node itself has been changed since its last reset (or created.)
+    pub fn changed(&self, id: NodeId) -> bool {
+        self.nodes[id.0].cd.changed()
+    }
+
+    /// Check if any node below this one has been changed since its last reset.
+    pub fn descendant_changed(&self, id: NodeId) -> bool {
+        self.nodes[id.0].descendant_dirty
+    }
+
+    /// Check if the node or any node below it has been changed since its last reset.
+    pub fn subtree_changed(&self, id: NodeId) -> bool {
+        self.changed(id) || self.descendant_changed(id)
+    }
+
+    /// Every changed node in the subtree of `id`, including `id`, in depth-first order.
+    /// Branches with no changes are not visited.
+    /// ```
+    /// use changed::CdTree;
+    /// let mut tree = CdTree::new(0);
+    /// let a = tree.add_child(tree.root(), 1);
+    /// let b = tree.add_child(a, 2);
+    /// let c = tree.add_child(tree.root(), 3);
+    /// *tree.get_mut(c) += 1;
+    /// *tree.get_mut(b) += 1;
+    /// *tree.get_mut(tree.root()) += 1;
+    /// let dirty: Vec<_> = tree.dirty_descendants(tree.root()).collect();
+    /// assert_eq!(dirty, [tree.root(), b, c]);
+    /// ```
+    pub fn dirty_descendants(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let mut stack = vec![id];
+        std::iter::from_fn(move || {
+            while let Some(id) = stack.pop() {
+                let node = &self.nodes[id.0];
+                if node.descendant_dirty {
+                    stack.extend(node.children.iter().rev().copied());
+                }
+                if node.cd.changed() {
+                    return Some(id);
+                }
+            }
+            None
+        })
+    }
+
+    /// Reset the change tracking of one node to false. Its descendants keep theirs.
+    pub fn reset(&mut self, id: NodeId) {
+        self.nodes[id.0].cd.reset();
+        self.clean_ancestors(id);
+    }
+
+    /// Reset the change tracking of a node and everything below it to false.
+    pub fn reset_subtree(&mut self, id: NodeId) {
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let node = &mut self.nodes[id.0];
+            node.cd.reset();
+            if node.descendant_dirty {
+                node.descendant_dirty = false;
+                stack.extend(node.children.iter().copied());
+            }
+        }
+        self.clean_ancestors(id);
+    }
+
+    /// Reset the change tracking of the whole tree to false.
+    pub fn reset_all(&mut self) {
+        self.reset_subtree(self.root());
+    }
+
+    fn mark_ancestors(&mut self, id: NodeId) {
+        let mut parent = self.nodes[id.0].parent;
+        while let Some(id) = parent {
+            let node = &mut self.nodes[id.0];
+            if node.descendant_dirty {
+                break;
+            }
+            node.descendant_dirty = true;
+            parent = node.parent;
+        }
+    }
+
+    /// Recompute the descendant-dirty bits above a node whose subtree got cleaner.
+    fn clean_ancestors(&mut self, id: NodeId) {
+        let mut parent = self.nodes[id.0].parent;
+        while let Some(id) = parent {
+            let dirty = self.nodes[id.0]
+                .children
+                .iter()
+                .any(|&child| self.subtree_changed(child));
+            let node = &mut self.nodes[id.0];
+            if node.descendant_dirty == dirty {
+                break;
+            }
+            node.descendant_dirty = dirty;
+            parent = node.parent;
+        }
+    }
+}
+
+/// Index does not trip change detection.
+impl<T> Index<NodeId> for CdTree<T> {
+    type Output = T;
+
+    fn index(&self, id: NodeId) -> &T {
+        self.get(id)
+    }
+}
+
+/// IndexMut trips change detection, like [`get_mut()`](CdTree::get_mut()).
+/// ```
+/// use changed::CdTree;
+/// let mut tree = CdTree::new(vec![1]);
+/// let child = tree.add_child(tree.root(), vec![2]);
+/// tree[child].push(3);
+/// assert!(tree.descendant_changed(tree.root()));
+/// ```
+impl<T> IndexMut<NodeId> for CdTree<T> {
+    fn index_mut(&mut self, id: NodeId) -> &mut T {
+        self.get_mut(id)
+    }
+}
+
+/// Impl default where the data impls default. Change detection is initialized to false.
+impl<T: Default> Default for CdTree<T> {
+    fn default() -> Self {
+        CdTree::new(T::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CdTree;
+
+    #[test]
+    fn resets_keep_ancestor_bits_consistent() {
+        let mut tree = CdTree::new(0);
+        let root = tree.root();
+        let a = tree.add_child(root, 0);
+        let a1 = tree.add_child(a, 0);
+        let a2 = tree.add_child(a, 0);
+        let b = tree.add_child(root, 0);
+
+        *tree.get_mut(a1) += 1;
+        *tree.get_mut(a2) += 1;
+        *tree.get_mut(b) += 1;
+
+        // One dirty child left under `a`.
+        tree.reset(a1);
+        assert!(tree.descendant_changed(a));
+        tree.reset(a2);
+        assert!(!tree.descendant_changed(a));
+        assert!(tree.descendant_changed(root));
+
+        *tree.get_mut(a1) += 1;
+        tree.reset_subtree(a);
+        assert!(!tree.subtree_changed(a));
+        assert!(tree.descendant_changed(root));
+        assert_eq!(tree.dirty_descendants(root).collect::<Vec<_>>(), [b]);
+
+        // A changed parent keeps its own flag when its subtree is clean.
+        *tree.get_mut(a) += 1;
+        tree.reset_subtree(b);
+        assert!(tree.descendant_changed(root));
+        tree.reset_all();
+        assert_eq!(tree.dirty_descendants(root).count(), 0);
+    }
+}
@@ -0,0 +1,437 @@
+use std::ops::{Deref, Index, IndexMut, Range};
+
+/// CdVec: Change Detection for each index of a `Vec`
+///
+/// Where a `Cd<Vec<T>>` marks the whole vector dirty, `CdVec` records which indices
+/// were touched since the last [`reset()`](CdVec::reset()), coalesced into as few
+/// ranges as possible. Elements that shift because of [`insert()`](CdVec::insert())
+/// or [`remove()`](CdVec::remove()) count as touched.
+///
+/// ```
+/// use changed::CdVec;
+/// let mut cd = CdVec::new(vec![0; 10]);
+/// cd[2] = 1;
+/// cd[3] = 1;
+/// cd[7] += 1;
+/// assert_eq!(cd.dirty_ranges(), [2..4, 7..8]);
+///
+/// cd.reset();
+/// assert!(!cd.changed());
+/// ```
+///
+/// Indices past the end are dropped from the ranges when the vector shrinks,
+/// so compare [`len()`](Vec::len()) with [`len_at_reset()`](CdVec::len_at_reset())
+/// to see whether it did.
+pub struct CdVec<T> {
+    data: Vec<T>,
+    dirty: RangeSet,
+    len_at_reset: usize,
+}
+
+impl<T> CdVec<T> {
+    /// Create a new CdVec with data.
+    /// It is initialized to false for change detection.
+    ///
+    /// ```
+    /// use changed::CdVec;
+    /// let cd = CdVec::new(vec![1, 2, 3]);
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn new(data: Vec<T>) -> CdVec<T> {
+        CdVec {
+            len_at_reset: data.len(),
+            data,
+            dirty: RangeSet::default(),
+        }
+    }
+
+    /// Create a new CdVec with data.
+    /// Every index is initialized to true for change detection.
+    /// ```
+    /// use changed::CdVec;
+    /// let cd = CdVec::new_true(vec![1, 2, 3]);
+    /// assert_eq!(cd.dirty_ranges(), [0..3]);
+    /// ```
+    pub fn new_true(data: Vec<T>) -> CdVec<T> {
+        let mut cd = CdVec::new(data);
+        cd.mark(0..cd.data.len());
+        cd
+    }
+
+    /// Reset the change tracking to false.
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new_true(vec![1, 2, 3]);
+    /// cd.pop();
+    /// cd.reset();
+    /// assert!(!cd.changed());
+    /// assert_eq!(cd.len_at_reset(), 2);
+    /// ```
+    pub fn reset(&mut self) {
+        self.dirty.clear();
+        self.len_at_reset = self.data.len();
+    }
+
+    /// Take the data out of the CdVec.
+    /// Consumes self and returns data.
+    /// ```
+    /// use changed::CdVec;
+    /// let cd = CdVec::new(vec![1, 2, 3]);
+    /// assert_eq!(cd.take(), [1, 2, 3]);
+    /// ```
+    pub fn take(self) -> Vec<T> {
+        self.data
+    }
+
+    /// Check if any index has been touched, or the length has changed,
+    /// since the last call to reset (or created.)
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new(vec![1, 2, 3]);
+    /// cd.truncate(1);
+    /// assert!(cd.dirty_ranges().is_empty());
+    /// assert!(cd.changed());
+    /// ```
+    pub fn changed(&self) -> bool {
+        !self.dirty.is_empty() || self.data.len() != self.len_at_reset
+    }
+
+    /// The touched indices, as sorted, non-overlapping, non-adjacent ranges.
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new(vec![0; 5]);
+    /// cd[1] = 1;
+    /// cd[3] = 1;
+    /// cd[2] = 1;
+    /// assert_eq!(cd.dirty_ranges(), [1..4]);
+    /// ```
+    pub fn dirty_ranges(&self) -> &[Range<usize>] {
+        &self.dirty.ranges
+    }
+
+    /// The length of the data at the last reset (or creation.)
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new(vec![1, 2, 3]);
+    /// cd.push(4);
+    /// assert_eq!(cd.len_at_reset(), 3);
+    /// ```
+    pub fn len_at_reset(&self) -> usize {
+        self.len_at_reset
+    }
+
+    /// Mutate the CdVec without tripping change detection.
+    ///
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new(vec![1, 2, 3]);
+    /// cd.mutate_silently()[0] = 5;
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn mutate_silently(&mut self) -> &mut Vec<T> {
+        &mut self.data
+    }
+
+    /// Mutably borrow the element at `index`, marking it dirty if it exists.
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new(vec![1, 2, 3]);
+    /// assert!(cd.get_mut(5).is_none());
+    /// assert!(!cd.changed());
+    /// *cd.get_mut(1).unwrap() = 5;
+    /// assert_eq!(cd.dirty_ranges(), [1..2]);
+    /// ```
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index < self.data.len() {
+            self.mark(index..index + 1);
+        }
+        self.data.get_mut(index)
+    }
+
+    /// Mutably borrow the elements in `range`, marking them dirty.
+    ///
+    /// # Panics
+    /// If `range` is out of bounds.
+    ///
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new(vec![3, 2, 1, 0]);
+    /// cd.slice_mut(0..3).sort();
+    /// assert_eq!(cd.dirty_ranges(), [0..3]);
+    /// ```
+    pub fn slice_mut(&mut self, range: Range<usize>) -> &mut [T] {
+        let slice = &mut self.data[range.clone()];
+        self.dirty.insert(range);
+        slice
+    }
+
+    /// Iterate mutably over the elements, marking all of them dirty.
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new(vec![1, 2, 3]);
+    /// cd.iter_mut().for_each(|v| *v *= 2);
+    /// assert_eq!(cd.dirty_ranges(), [0..3]);
+    /// ```
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.mark(0..self.data.len());
+        self.data.iter_mut()
+    }
+
+    /// Append an element, marking its index dirty.
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new(vec![1, 2, 3]);
+    /// cd.push(4);
+    /// assert_eq!(cd.dirty_ranges(), [3..4]);
+    /// ```
+    pub fn push(&mut self, value: T) {
+        self.mark(self.data.len()..self.data.len() + 1);
+        self.data.push(value);
+    }
+
+    /// Remove the last element and return it, if there is one.
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new(vec![1, 2, 3]);
+    /// assert_eq!(cd.pop(), Some(3));
+    /// assert!(cd.changed());
+    /// ```
+    pub fn pop(&mut self) -> Option<T> {
+        let value = self.data.pop();
+        self.dirty.clip(self.data.len());
+        value
+    }
+
+    /// Insert an element at `index`, marking it and every element after it dirty.
+    ///
+    /// # Panics
+    /// If `index > len`.
+    ///
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new(vec![1, 2, 3]);
+    /// cd.insert(1, 5);
+    /// assert_eq!(cd.dirty_ranges(), [1..4]);
+    /// ```
+    pub fn insert(&mut self, index: usize, value: T) {
+        self.data.insert(index, value);
+        self.mark(index..self.data.len());
+    }
+
+    /// Remove the element at `index`, marking every element after it dirty.
+    ///
+    /// # Panics
+    /// If `index` is out of bounds.
+    ///
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new(vec![1, 2, 3, 4]);
+    /// assert_eq!(cd.remove(1), 2);
+    /// assert_eq!(cd.dirty_ranges(), [1..3]);
+    /// ```
+    pub fn remove(&mut self, index: usize) -> T {
+        let value = self.data.remove(index);
+        self.dirty.clip(self.data.len());
+        self.mark(index..self.data.len());
+        value
+    }
+
+    /// Remove the element at `index`, replacing it with the last element.
+    ///
+    /// # Panics
+    /// If `index` is out of bounds.
+    ///
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new(vec![1, 2, 3, 4]);
+    /// assert_eq!(cd.swap_remove(1), 2);
+    /// assert_eq!(cd.dirty_ranges(), [1..2]);
+    /// ```
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        let value = self.data.swap_remove(index);
+        self.dirty.clip(self.data.len());
+        if index < self.data.len() {
+            self.mark(index..index + 1);
+        }
+        value
+    }
+
+    /// Shorten the vector to `len` elements.
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new_true(vec![1, 2, 3, 4]);
+    /// cd.truncate(2);
+    /// assert_eq!(cd.dirty_ranges(), [0..2]);
+    /// ```
+    pub fn truncate(&mut self, len: usize) {
+        self.data.truncate(len);
+        self.dirty.clip(self.data.len());
+    }
+
+    /// Remove every element.
+    /// ```
+    /// use changed::CdVec;
+    /// let mut cd = CdVec::new(vec![1, 2, 3]);
+    /// cd.clear();
+    /// assert!(cd.changed());
+    /// ```
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    fn mark(&mut self, range: Range<usize>) {
+        self.dirty.insert(range);
+    }
+}
+
+/// Append elements, marking their indices dirty.
+/// ```
+/// use changed::CdVec;
+/// let mut cd = CdVec::new(vec![1]);
+/// cd.extend(vec![2, 3]);
+/// assert_eq!(cd.dirty_ranges(), [1..3]);
+/// ```
+impl<T> Extend<T> for CdVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let start = self.data.len();
+        self.data.extend(iter);
+        self.mark(start..self.data.len());
+    }
+}
+
+/// deref does not trip change detection.
+/// ```
+/// use changed::CdVec;
+/// let cd = CdVec::new(vec![1, 2, 3]);
+/// assert_eq!(cd.len(), 3);
+/// assert_eq!(cd[0], 1);
+/// assert!(!cd.changed());
+/// ```
+impl<T> Deref for CdVec<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl<T> Index<usize> for CdVec<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.data[index]
+    }
+}
+
+/// index_mut marks the index dirty.
+/// ```
+/// use changed::CdVec;
+/// let mut cd = CdVec::new(vec![1, 2, 3]);
+/// cd[1] += 5;
+/// assert_eq!(cd.dirty_ranges(), [1..2]);
+/// ```
+impl<T> IndexMut<usize> for CdVec<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        let value = &mut self.data[index];
+        self.dirty.insert(index..index + 1);
+        value
+    }
+}
+
+/// Impl default. Change detection is initialized to false.
+/// ```
+/// use changed::CdVec;
+/// let empty: CdVec<i32> = CdVec::default();
+/// assert!(!empty.changed());
+/// ```
+impl<T> Default for CdVec<T> {
+    fn default() -> Self {
+        CdVec::new(Vec::new())
+    }
+}
+
+impl<T> From<Vec<T>> for CdVec<T> {
+    fn from(data: Vec<T>) -> Self {
+        CdVec::new(data)
+    }
+}
+
+/// Sorted, non-overlapping, non-adjacent ranges.
+#[derive(Debug, Default, Clone)]
+struct RangeSet {
+    ranges: Vec<Range<usize>>,
+}
+
+impl RangeSet {
+    fn insert(&mut self, range: Range<usize>) {
+        if range.start >= range.end {
+            return;
+        }
+        // Every range from `first` to `last` overlaps or touches the new one.
+        let first = self.ranges.partition_point(|r| r.end < range.start);
+        let last = self.ranges.partition_point(|r| r.start <= range.end);
+        if first == last {
+            self.ranges.insert(first, range);
+            return;
+        }
+        let start = range.start.min(self.ranges[first].start);
+        let end = range.end.max(self.ranges[last - 1].end);
+        self.ranges.splice(first..last, std::iter::once(start..end));
+    }
+
+    fn clip(&mut self, len: usize) {
+        let keep = self.ranges.partition_point(|r| r.start < len);
+        self.ranges.truncate(keep);
+        if let Some(last) = self.ranges.last_mut() {
+            last.end = last.end.min(len);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.ranges.clear();
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use super::RangeSet;
+    use crate::CdVec;
+
+    #[test]
+    fn ranges_coalesce() {
+        let mut set = RangeSet::default();
+        set.insert(5..6);
+        set.insert(1..2);
+        set.insert(8..10);
+        assert_eq!(set.ranges, [1..2, 5..6, 8..10]);
+        set.insert(6..8);
+        assert_eq!(set.ranges, [1..2, 5..10]);
+        set.insert(0..1);
+        assert_eq!(set.ranges, [0..2, 5..10]);
+        set.insert(3..3);
+        assert_eq!(set.ranges, [0..2, 5..10]);
+        set.insert(1..12);
+        assert_eq!(set.ranges, [0..12]);
+        set.clip(4);
+        assert_eq!(set.ranges, [0..4]);
+        set.clip(0);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn shrinking_then_growing() {
+        let mut cd = CdVec::new(vec![0; 4]);
+        cd.pop();
+        cd.pop();
+        assert!(cd.dirty_ranges().is_empty());
+        cd.push(1);
+        cd.push(1);
+        assert_eq!(cd.dirty_ranges(), [2..4]);
+        assert_eq!(cd.len(), cd.len_at_reset());
+    }
+}
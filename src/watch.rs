@@ -0,0 +1,485 @@
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+use crate::{Cd, ChangeDetector, FlagDetector};
+
+/// CdWatch: Change Detection that can be awaited
+///
+/// A [`Cd`] that wakes its [`Watcher`]s when it changes.
+/// With the default [`FlagDetector`] every `deref_mut()` is a change and wakes them.
+/// Other detectors are mutated through [`modify()`](CdWatch::modify()), which only
+/// wakes watchers if the detector sees a change made through the guard.
+/// Watchers only use [`std::task`], so they work with any executor,
+/// and they are `Send`, so they can wait on another thread.
+///
+/// ```
+/// use changed::CdWatch;
+/// # use std::future::Future;
+/// # use std::pin::pin;
+/// # use std::sync::Arc;
+/// # use std::task::{Context, Poll, Wake};
+/// # struct Unpark(std::thread::Thread);
+/// # impl Wake for Unpark {
+/// #     fn wake(self: Arc<Self>) { self.0.unpark() }
+/// # }
+/// # fn block_on<F: Future>(future: F) -> F::Output {
+/// #     let mut future = pin!(future);
+/// #     let waker = Arc::new(Unpark(std::thread::current())).into();
+/// #     let mut cx = Context::from_waker(&waker);
+/// #     loop {
+/// #         match future.as_mut().poll(&mut cx) {
+/// #             Poll::Ready(out) => return out,
+/// #             Poll::Pending => std::thread::park(),
+/// #         }
+/// #     }
+/// # }
+///
+/// let mut cd = CdWatch::new(5);
+/// let mut watcher = cd.watch();
+///
+/// let thread = std::thread::spawn(move || {
+///     block_on(watcher.wait_changed()).unwrap();
+/// });
+///
+/// *cd += 5;
+/// thread.join().unwrap();
+/// ```
+pub struct CdWatch<T, D = FlagDetector> {
+    cd: Cd<T, D>,
+    shared: Owner,
+}
+
+/// Closes the watchers when the CdWatch goes away.
+struct Owner(Arc<Shared>);
+
+impl Deref for Owner {
+    type Target = Shared;
+
+    fn deref(&self) -> &Shared {
+        &self.0
+    }
+}
+
+impl Drop for Owner {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.notify();
+    }
+}
+
+struct Shared {
+    version: AtomicU64,
+    closed: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl Shared {
+    fn bump(&self) {
+        self.version.fetch_add(1, Ordering::SeqCst);
+        self.notify();
+    }
+
+    fn notify(&self) {
+        let wakers =
+            std::mem::take(&mut *self.wakers.lock().unwrap_or_else(PoisonError::into_inner));
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> CdWatch<T> {
+    /// Create a new CdWatch with data.
+    /// It is initialized to false for change detection.
+    ///
+    /// ```
+    /// use changed::CdWatch;
+    /// let cd = CdWatch::new(5);
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn new(data: T) -> CdWatch<T> {
+        CdWatch::from_cd(Cd::new(data))
+    }
+
+    /// Create a new CdWatch with data.
+    /// It is initialized to true for change detection.
+    /// ```
+    /// use changed::CdWatch;
+    /// let cd = CdWatch::new_true(5);
+    /// assert!(cd.changed());
+    /// ```
+    pub fn new_true(data: T) -> CdWatch<T> {
+        CdWatch::from_cd(Cd::new_true(data))
+    }
+}
+
+impl<T, D: ChangeDetector<T>> CdWatch<T, D> {
+    /// Create a new CdWatch with data, using detector for change detection.
+    /// It is initialized to false for change detection.
+    ///
+    /// ```
+    /// use changed::{CdWatch, EqDetector};
+    /// let cd = CdWatch::with_detector(5, EqDetector::new());
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn with_detector(data: T, detector: D) -> CdWatch<T, D> {
+        CdWatch::from_cd(Cd::with_detector(data, detector))
+    }
+
+    fn from_cd(cd: Cd<T, D>) -> CdWatch<T, D> {
+        CdWatch {
+            cd,
+            shared: Owner(Arc::new(Shared {
+                version: AtomicU64::new(0),
+                closed: AtomicBool::new(false),
+                wakers: Mutex::new(Vec::new()),
+            })),
+        }
+    }
+
+    /// Create a watcher that is woken by every change from now on.
+    /// ```
+    /// use changed::CdWatch;
+    /// let mut cd = CdWatch::new(5);
+    /// let watcher = cd.watch();
+    /// assert!(!watcher.has_changed());
+    /// *cd += 1;
+    /// assert!(watcher.has_changed());
+    /// ```
+    pub fn watch(&self) -> Watcher {
+        Watcher {
+            seen: self.shared.version.load(Ordering::SeqCst),
+            shared: Arc::clone(&self.shared.0),
+        }
+    }
+
+    /// Borrow the data mutably. Watchers are woken when the guard is dropped,
+    /// if the data counts as changed.
+    ///
+    /// If the data had already changed, the guard checks a clone of the detector,
+    /// reset to the data as it was before the guard, so that writing the same value
+    /// again does not wake the watchers again.
+    /// ```
+    /// use changed::{CdWatch, EqDetector};
+    /// let mut cd = CdWatch::with_detector(5, EqDetector::new());
+    /// let mut watcher = cd.watch();
+    ///
+    /// // Same value, so EqDetector does not count it as a change.
+    /// *cd.modify() = 5;
+    /// assert!(!watcher.has_changed());
+    /// *cd.modify() = 6;
+    /// assert!(watcher.has_changed());
+    ///
+    /// watcher.mark_seen();
+    /// *cd.modify() = 6;
+    /// assert!(!watcher.has_changed());
+    /// ```
+    pub fn modify(&mut self) -> WatchGuard<'_, T, D>
+    where
+        D: Clone,
+    {
+        WatchGuard {
+            was_changed: self.changed(),
+            watched: self,
+            touched: false,
+            probe: None,
+        }
+    }
+
+    /// Reset the change tracking to false. Watchers are not affected.
+    /// ```
+    /// use changed::CdWatch;
+    /// let mut cd = CdWatch::new_true(5);
+    /// cd.reset();
+    /// assert!(!cd.changed());
+    /// ```
+    pub fn reset(&mut self) {
+        self.cd.reset();
+    }
+
+    /// Check if the CdWatch has been changed since the last call to reset (or created.)
+    /// ```
+    /// use changed::CdWatch;
+    /// let mut cd = CdWatch::new(5);
+    /// *cd += 5;
+    /// assert!(cd.changed());
+    /// ```
+    pub fn changed(&self) -> bool {
+        self.cd.changed()
+    }
+
+    /// Take the data out of the CdWatch.
+    /// Consumes self and returns data. Waiting watchers resolve with [`WatchClosed`].
+    /// ```
+    /// use changed::CdWatch;
+    /// let cd = CdWatch::new(5);
+    /// assert_eq!(cd.take(), 5);
+    /// ```
+    pub fn take(self) -> T {
+        self.cd.take()
+    }
+
+    /// Mutate the CdWatch without tripping change detection or waking watchers.
+    ///
+    /// ```
+    /// use changed::CdWatch;
+    /// let mut cd = CdWatch::new(5);
+    /// let watcher = cd.watch();
+    /// *cd.mutate_silently() += 5;
+    /// assert!(!cd.changed());
+    /// assert!(!watcher.has_changed());
+    /// ```
+    pub fn mutate_silently(&mut self) -> &mut T {
+        self.cd.mutate_silently()
+    }
+}
+
+/// deref does not trip change detection.
+/// ```
+/// use changed::CdWatch;
+/// let cd = CdWatch::new(5);
+/// assert_eq!(*cd, 5);
+/// assert!(!cd.changed());
+/// ```
+impl<T, D> Deref for CdWatch<T, D> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.cd
+    }
+}
+
+/// deref_mut trips change detection and wakes every watcher.
+/// It is only implemented for the default [`FlagDetector`], for which every `deref_mut()`
+/// is a change; use [`modify()`](CdWatch::modify()) with other detectors.
+/// ```
+/// use changed::CdWatch;
+/// let mut cd = CdWatch::new(5);
+/// let watcher = cd.watch();
+/// *cd += 5;
+/// assert!(cd.changed());
+/// assert!(watcher.has_changed());
+/// ```
+impl<T> DerefMut for CdWatch<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.shared.bump();
+        &mut self.cd
+    }
+}
+
+/// Mutable access to the data of a [`CdWatch`], from [`modify()`](CdWatch::modify()).
+///
+/// `deref_mut()` trips change detection, and dropping the guard wakes the watchers.
+pub struct WatchGuard<'a, T, D: ChangeDetector<T>> {
+    watched: &'a mut CdWatch<T, D>,
+    was_changed: bool,
+    touched: bool,
+    /// Only sees the changes made through this guard, if the data had already changed.
+    probe: Option<D>,
+}
+
+impl<T, D: ChangeDetector<T>> Deref for WatchGuard<'_, T, D> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.watched.cd
+    }
+}
+
+impl<T, D: ChangeDetector<T> + Clone> DerefMut for WatchGuard<'_, T, D> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        let cd = &mut self.watched.cd;
+        if !self.touched && self.was_changed {
+            let mut probe = cd.detector.clone();
+            probe.on_reset(&cd.data);
+            self.probe = Some(probe);
+        }
+        self.touched = true;
+        if let Some(probe) = &mut self.probe {
+            probe.on_mutate(&cd.data);
+        }
+        cd
+    }
+}
+
+impl<T, D: ChangeDetector<T>> Drop for WatchGuard<'_, T, D> {
+    fn drop(&mut self) {
+        if !self.touched || !self.watched.changed() {
+            return;
+        }
+        let changed_here = match &self.probe {
+            Some(probe) => probe.is_changed(&self.watched.cd.data),
+            None => true,
+        };
+        if changed_here {
+            self.watched.shared.bump();
+        }
+    }
+}
+
+/// Impl default where the data and detector impl default. Change detection is initialized to false.
+/// ```
+/// use changed::CdWatch;
+/// let zero: CdWatch<i32> = CdWatch::default();
+/// assert!(!zero.changed());
+/// ```
+impl<T: Default, D: ChangeDetector<T> + Default> Default for CdWatch<T, D> {
+    fn default() -> Self {
+        CdWatch::from_cd(Cd::default())
+    }
+}
+
+/// Waits for changes to a [`CdWatch`]. Created with [`watch()`](CdWatch::watch()).
+///
+/// Every watcher keeps track of the changes it has seen on its own.
+#[derive(Clone)]
+pub struct Watcher {
+    shared: Arc<Shared>,
+    seen: u64,
+}
+
+impl Watcher {
+    /// Check if the CdWatch has been changed since this watcher last saw it.
+    pub fn has_changed(&self) -> bool {
+        self.shared.version.load(Ordering::SeqCst) != self.seen
+    }
+
+    /// Mark every change so far as seen.
+    /// ```
+    /// use changed::CdWatch;
+    /// let mut cd = CdWatch::new(5);
+    /// let mut watcher = cd.watch();
+    /// *cd += 1;
+    /// watcher.mark_seen();
+    /// assert!(!watcher.has_changed());
+    /// ```
+    pub fn mark_seen(&mut self) {
+        self.seen = self.shared.version.load(Ordering::SeqCst);
+    }
+
+    /// Wait until the CdWatch is changed, then mark the change as seen.
+    /// Resolves immediately if there is a change this watcher has not seen yet.
+    ///
+    /// Resolves with [`WatchClosed`] once the CdWatch is gone.
+    pub fn wait_changed(&mut self) -> WaitChanged<'_> {
+        WaitChanged { watcher: self }
+    }
+}
+
+/// Future returned by [`Watcher::wait_changed()`].
+pub struct WaitChanged<'a> {
+    watcher: &'a mut Watcher,
+}
+
+impl Future for WaitChanged<'_> {
+    type Output = Result<(), WatchClosed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let watcher = &mut *self.get_mut().watcher;
+        let shared = &*watcher.shared;
+        // Hold the lock while checking, so a change cannot slip in before the waker is stored.
+        let mut wakers = shared.wakers.lock().unwrap_or_else(PoisonError::into_inner);
+        let version = shared.version.load(Ordering::SeqCst);
+        if version != watcher.seen {
+            watcher.seen = version;
+            return Poll::Ready(Ok(()));
+        }
+        if shared.closed.load(Ordering::SeqCst) {
+            return Poll::Ready(Err(WatchClosed));
+        }
+        if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// The [`CdWatch`] behind a [`Watcher`] was dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchClosed;
+
+impl fmt::Display for WatchClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the watched value was dropped")
+    }
+}
+
+impl Error for WatchClosed {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CdWatch, EqDetector, WatchClosed};
+    use std::future::Future;
+    use std::pin::pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    #[derive(Default)]
+    struct CountWakes(AtomicUsize);
+
+    impl Wake for CountWakes {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn wakes_on_deref_mut() {
+        let mut cd = CdWatch::new(5);
+        let mut watcher = cd.watch();
+        let wakes = Arc::new(CountWakes::default());
+        let waker = Waker::from(Arc::clone(&wakes));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut future = pin!(watcher.wait_changed());
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+        let _ = *cd;
+        cd.reset();
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 0);
+
+        *cd += 1;
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+        assert!(!watcher.has_changed());
+    }
+
+    #[test]
+    fn resolves_when_dropped() {
+        let cd = CdWatch::new(5);
+        let mut watcher = cd.watch();
+        let waker = Waker::from(Arc::new(CountWakes::default()));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut future = pin!(watcher.wait_changed());
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(cd.take(), 5);
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(Err(WatchClosed)));
+    }
+    #[test]
+    fn same_value_writes_do_not_wake() {
+        let mut cd = CdWatch::with_detector(5, EqDetector::new());
+        let mut watcher = cd.watch();
+        let wakes = Arc::new(CountWakes::default());
+        let waker = Waker::from(Arc::clone(&wakes));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut future = pin!(watcher.wait_changed());
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+        *cd.modify() = 5;
+        let _ = *cd.modify();
+        assert!(!cd.changed());
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 0);
+
+        *cd.modify() += 1;
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+    }
+}
@@ -0,0 +1,63 @@
+//! Run with `RUSTFLAGS="--cfg loom" cargo test --test loom --release`
+#![cfg(loom)]
+
+use changed::SyncCd;
+use loom::sync::Arc;
+use loom::thread;
+
+#[test]
+fn reset_then_read_never_loses_a_write() {
+    loom::model(|| {
+        let cd = Arc::new(SyncCd::new(0));
+        let writer = Arc::clone(&cd);
+        let thread = thread::spawn(move || *writer.write() += 1);
+
+        cd.reset();
+        let seen = *cd.read();
+        thread.join().unwrap();
+
+        // Either the read saw the write, or the write is still flagged.
+        assert!(seen == 1 || cd.changed());
+    });
+}
+
+#[test]
+fn changed_implies_write_is_visible() {
+    loom::model(|| {
+        let cd = Arc::new(SyncCd::new(0));
+        let writer = Arc::clone(&cd);
+        let thread = thread::spawn(move || *writer.write() = 1);
+
+        if cd.changed() {
+            assert_eq!(*cd.read(), 1);
+        }
+        thread.join().unwrap();
+        assert!(cd.changed());
+    });
+}
+
+#[test]
+fn silent_writes_and_reads_do_not_trip() {
+    loom::model(|| {
+        let cd = Arc::new(SyncCd::new(0));
+        let writer = Arc::clone(&cd);
+        let thread = thread::spawn(move || *writer.write_silently() += 1);
+
+        let _ = *cd.read();
+        thread.join().unwrap();
+        assert!(!cd.changed());
+        assert_eq!(*cd.read(), 1);
+    });
+}
+
+#[test]
+fn wait_until_sees_the_write() {
+    loom::model(|| {
+        let cd = Arc::new(SyncCd::new(0));
+        let writer = Arc::clone(&cd);
+        let thread = thread::spawn(move || *writer.write() = 1);
+
+        assert_eq!(*cd.wait_until(|v| *v == 1), 1);
+        thread.join().unwrap();
+    });
+}